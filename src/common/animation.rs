@@ -0,0 +1,146 @@
+use std::io;
+use std::io::prelude::*;
+use std::io::IsTerminal;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+pub trait Animation {
+    fn frame(&self) -> String;
+
+    // Moves to the next frame, returns false when the animation is over
+    fn advance(&mut self) -> bool;
+}
+
+const CTRL_C: u8 = 0x03;
+
+enum Key {
+    TogglePause,
+    Step,
+    Quit
+}
+
+// Puts the controlling terminal into non-canonical, non-blocking mode via `stty`
+// and restores the original settings when dropped. Signal keys are turned off too, so Ctrl-C
+// arrives as a byte and quits normally instead of leaving the cursor hidden and echo off
+struct RawInput {
+    saved_settings: Option<String>
+}
+
+fn stty(args: &[&str]) -> Option<String> {
+    Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+impl RawInput {
+    fn enable() -> Self {
+        let saved_settings =
+            if io::stdin().is_terminal() {
+                stty(&["-g"]).and_then(|saved| stty(&["-icanon", "-echo", "-isig", "min", "0", "time", "0"]).map(|_| saved))
+            } else {
+                None
+            };
+
+        RawInput { saved_settings }
+    }
+
+    fn poll(&self) -> Option<Key> {
+        self.saved_settings.as_ref()?;
+
+        let mut buf = [0u8; 1];
+        match io::stdin().read(&mut buf) {
+            Ok(1) => match buf[0] {
+                b' ' | b'p' => Some(Key::TogglePause),
+                b'n' | b's' => Some(Key::Step),
+                b'q' | CTRL_C => Some(Key::Quit),
+                _ => None
+            },
+            _ => None
+        }
+    }
+}
+
+impl Drop for RawInput {
+    fn drop(&mut self) {
+        if let Some(ref saved) = self.saved_settings {
+            stty(&[saved.as_str()]);
+        }
+    }
+}
+
+fn play_on_terminal<A: Animation>(animation: &mut A, fps: f64) {
+    let frame_time = Duration::from_millis((1000.0 / fps) as u64);
+    let tick = Duration::from_millis(10);
+    let input = RawInput::enable();
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let mut frame_idx = 0;
+    let mut paused = false;
+    let mut running = true;
+
+    write!(out, "\x1b[?25l\x1b[2J").unwrap();
+    while running {
+        let lines: Vec<String> = animation.frame().lines().map(|line| format!("{}\x1b[K", line)).collect();
+        write!(out, "\x1b[H{}\n\x1b[K\nframe {}{} | space: pause, n: step, q: quit\x1b[J",
+               lines.join("\n"), frame_idx, if paused { " (paused)" } else { "" }).unwrap();
+        out.flush().unwrap();
+
+        let mut next_frame = false;
+        let mut waited = Duration::from_millis(0);
+        loop {
+            match input.poll() {
+                Some(Key::TogglePause) => {
+                    paused = !paused;
+                    break;
+                },
+                Some(Key::Step) => if paused {
+                    next_frame = true;
+                    break;
+                },
+                Some(Key::Quit) => {
+                    running = false;
+                    break;
+                },
+                None => if !paused && waited >= frame_time {
+                    next_frame = true;
+                    break;
+                } else {
+                    thread::sleep(tick);
+                    waited += tick;
+                }
+            }
+        }
+
+        if next_frame {
+            running = animation.advance();
+            frame_idx += 1;
+        }
+    }
+    writeln!(out, "\x1b[?25h").unwrap();
+}
+
+fn play_as_lines<A: Animation>(animation: &mut A) {
+    let mut frame_idx = 0;
+    loop {
+        println!("frame {}", frame_idx);
+        println!("{}", animation.frame());
+        if !animation.advance() {
+            break;
+        }
+        frame_idx += 1;
+    }
+}
+
+// Redraws the frames in place on a terminal, and prints them one after the other otherwise
+pub fn play<A: Animation>(animation: &mut A, fps: f64) {
+    if io::stdout().is_terminal() {
+        play_on_terminal(animation, fps);
+    } else {
+        play_as_lines(animation);
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// All finders return (mu, lambda): the index of the first state on the loop and the loop length
+
+#[allow(dead_code)]
+pub fn floyd<T, F>(f: F, x0: &T) -> (usize, usize)
+    where T: Clone + PartialEq,
+          F: Fn(&T) -> T {
+    let mut tortoise = f(x0);
+    let mut hare = f(&tortoise);
+    while tortoise != hare {
+        tortoise = f(&tortoise);
+        hare = f(&f(&hare));
+    }
+
+    let mut mu = 0;
+    tortoise = x0.clone();
+    while tortoise != hare {
+        tortoise = f(&tortoise);
+        hare = f(&hare);
+        mu += 1;
+    }
+
+    let mut lambda = 1;
+    hare = f(&tortoise);
+    while tortoise != hare {
+        hare = f(&hare);
+        lambda += 1;
+    }
+
+    (mu, lambda)
+}
+
+pub fn brent<T, F>(f: F, x0: &T) -> (usize, usize)
+    where T: Clone + PartialEq,
+          F: Fn(&T) -> T {
+    let mut power = 1;
+    let mut lambda = 1;
+    let mut tortoise = x0.clone();
+    let mut hare = f(x0);
+    while tortoise != hare {
+        if power == lambda {
+            tortoise = hare.clone();
+            power *= 2;
+            lambda = 0;
+        }
+        hare = f(&hare);
+        lambda += 1;
+    }
+
+    let mut mu = 0;
+    tortoise = x0.clone();
+    hare = x0.clone();
+    for _ in 0..lambda {
+        hare = f(&hare);
+    }
+    while tortoise != hare {
+        tortoise = f(&tortoise);
+        hare = f(&hare);
+        mu += 1;
+    }
+
+    (mu, lambda)
+}
+
+pub fn hashing<T, F>(f: F, x0: &T) -> (usize, usize)
+    where T: Clone + Eq + Hash,
+          F: Fn(&T) -> T {
+    let mut seen: HashMap<T, usize> = HashMap::new();
+    let mut state = x0.clone();
+    let mut idx = 0;
+
+    loop {
+        if let Some(&first) = seen.get(&state) {
+            return (first, idx - first);
+        }
+        let next = f(&state);
+        seen.insert(state, idx);
+        state = next;
+        idx += 1;
+    }
+}
+
+fn iterate<T, F>(f: &F, x0: &T, n: usize) -> T
+    where T: Clone,
+          F: Fn(&T) -> T {
+    let mut state = x0.clone();
+    for _ in 0..n {
+        state = f(&state);
+    }
+
+    state
+}
+
+// Returns the state after applying `f` n times, skipping over whole loops once the cycle is known
+pub fn nth_state<T, F>(f: F, x0: &T, n: usize) -> T
+    where T: Clone + PartialEq,
+          F: Fn(&T) -> T {
+    let (mu, lambda) = brent(&f, x0);
+    if n < mu + lambda {
+        iterate(&f, x0, n)
+    } else {
+        iterate(&f, x0, mu + (n - mu) % lambda)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 0 -> 1 -> 2 -> 3 -> 4 -> 2 -> ..., so mu is 2 and lambda is 3
+    fn step(n: &u32) -> u32 {
+        if *n == 4 { 2 } else { n + 1 }
+    }
+
+    #[test]
+    fn finders_agree() {
+        assert_eq!(floyd(step, &0), (2, 3));
+        assert_eq!(brent(step, &0), (2, 3));
+        assert_eq!(hashing(step, &0), (2, 3));
+
+        // A fixed point loops right away
+        assert_eq!(floyd(|&n: &u32| n, &7), (0, 1));
+        assert_eq!(brent(|&n: &u32| n, &7), (0, 1));
+        assert_eq!(hashing(|&n: &u32| n, &7), (0, 1));
+
+        let lcg = |&n: &u64| (n * 17 + 5) % 1000;
+        assert_eq!(floyd(lcg, &3), hashing(lcg, &3));
+        assert_eq!(brent(lcg, &3), hashing(lcg, &3));
+    }
+
+    #[test]
+    fn nth_state_skips_loops() {
+        for n in 0..20 {
+            assert_eq!(nth_state(step, &0, n), iterate(&step, &0, n));
+        }
+        assert_eq!(nth_state(step, &0, 1_000_000_000), 4);
+    }
+}
@@ -0,0 +1,292 @@
+use std::fmt;
+use std::ops::{Index, IndexMut};
+use std::str::FromStr;
+
+#[allow(dead_code)]
+#[derive(Debug)]
+pub enum Error {
+    CouldNotParseCell(usize, usize, String),
+    EmptyInput
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>
+}
+
+const OFFSETS_4: [(isize, isize); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+#[allow(dead_code)]
+const OFFSETS_8: [(isize, isize); 8] = [(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, -1), (-1, 0), (-1, 1)];
+
+impl<T: Clone> Grid<T> {
+    #[allow(dead_code)]
+    pub fn new(height: usize, width: usize, value: T) -> Self {
+        Grid {
+            width,
+            height,
+            cells: vec![value; width * height]
+        }
+    }
+
+    // Shorter rows are padded with `fill`, so ragged input still gives a rectangular grid
+    pub fn from_rows(rows: Vec<Vec<T>>, fill: T) -> Self {
+        let height = rows.len();
+        let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+        let mut cells = Vec::with_capacity(width * height);
+
+        for row in rows {
+            let missing = width - row.len();
+            cells.extend(row);
+            cells.extend(vec![fill.clone(); missing]);
+        }
+
+        Grid { width, height, cells }
+    }
+
+    #[allow(dead_code)]
+    pub fn transpose(&self) -> Grid<T> {
+        let mut cells = Vec::with_capacity(self.cells.len());
+        for col in 0..self.width {
+            cells.extend(self.column(col).cloned());
+        }
+
+        Grid { width: self.height, height: self.width, cells }
+    }
+
+    #[allow(dead_code)]
+    pub fn rotate_clockwise(&self) -> Grid<T> {
+        let mut cells = Vec::with_capacity(self.cells.len());
+        for col in 0..self.width {
+            cells.extend(self.column(col).rev().cloned());
+        }
+
+        Grid { width: self.height, height: self.width, cells }
+    }
+
+    #[allow(dead_code)]
+    pub fn rotate_counter_clockwise(&self) -> Grid<T> {
+        let mut cells = Vec::with_capacity(self.cells.len());
+        for col in (0..self.width).rev() {
+            cells.extend(self.column(col).cloned());
+        }
+
+        Grid { width: self.height, height: self.width, cells }
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn contains(&self, row: isize, col: isize) -> bool {
+        row >= 0 && col >= 0 && (row as usize) < self.height && (col as usize) < self.width
+    }
+
+    pub fn get(&self, row: isize, col: isize) -> Option<&T> {
+        if self.contains(row, col) {
+            Some(&self.cells[row as usize * self.width + col as usize])
+        } else {
+            None
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn get_mut(&mut self, row: isize, col: isize) -> Option<&mut T> {
+        if self.contains(row, col) {
+            Some(&mut self.cells[row as usize * self.width + col as usize])
+        } else {
+            None
+        }
+    }
+
+    pub fn row(&self, row: usize) -> &[T] {
+        &self.cells[row * self.width..(row + 1) * self.width]
+    }
+
+    pub fn rows(&self) -> Vec<&[T]> {
+        (0..self.height).map(|row| self.row(row)).collect()
+    }
+
+    #[allow(dead_code)]
+    pub fn column<'a>(&'a self, col: usize) -> impl DoubleEndedIterator<Item=&'a T> + 'a {
+        assert!(col < self.width);
+        self.cells.iter().skip(col).step_by(self.width)
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item=(usize, usize)> {
+        let width = self.width;
+        (0..self.height * width).map(move |idx| (idx / width, idx % width))
+    }
+
+    pub fn neighbours4(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
+        self.neighbours_by(row, col, &OFFSETS_4)
+    }
+
+    #[allow(dead_code)]
+    pub fn neighbours8(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
+        self.neighbours_by(row, col, &OFFSETS_8)
+    }
+
+    fn neighbours_by(&self, row: usize, col: usize, offsets: &[(isize, isize)]) -> Vec<(usize, usize)> {
+        offsets.iter()
+            .map(|&(dr, dc)| (row as isize + dr, col as isize + dc))
+            .filter(|&(r, c)| self.contains(r, c))
+            .map(|(r, c)| (r as usize, c as usize))
+            .collect()
+    }
+
+    #[allow(dead_code)]
+    pub fn map<U, F: Fn(&T) -> U>(&self, f: F) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect()
+        }
+    }
+
+    pub fn render<F: Fn(&T) -> char>(&self, f: F) -> String {
+        let lines: Vec<String> = self.rows().iter().map(|row| row.iter().map(&f).collect()).collect();
+        lines.join("\n")
+    }
+}
+
+impl Grid<char> {
+    pub fn parse_chars(input: &str) -> Grid<char> {
+        Grid::from_rows(input.split('\n').map(|line| line.chars().collect()).collect(), ' ')
+    }
+}
+
+impl<T: Clone + Default + FromStr> Grid<T> {
+    #[allow(dead_code)]
+    pub fn parse_whitespace_separated(input: &str) -> Result<Grid<T>, Error> {
+        if input.trim().is_empty() {
+            return Err(Error::EmptyInput);
+        }
+
+        let mut rows: Vec<Vec<T>> = Vec::new();
+        for (row_idx, line) in input.lines().enumerate() {
+            let mut row = Vec::new();
+            for (col_idx, s) in line.split_whitespace().enumerate() {
+                let value = s.parse::<T>().map_err(|_| Error::CouldNotParseCell(row_idx, col_idx, String::from(s)))?;
+                row.push(value);
+            }
+            rows.push(row);
+        }
+
+        Ok(Grid::from_rows(rows, T::default()))
+    }
+}
+
+impl<T> Index<(usize, usize)> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, (row, col): (usize, usize)) -> &T {
+        assert!(row < self.height && col < self.width, "({}, {}) is outside of the grid", row, col);
+        &self.cells[row * self.width + col]
+    }
+}
+
+impl<T> IndexMut<(usize, usize)> for Grid<T> {
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut T {
+        assert!(row < self.height && col < self.width, "({}, {}) is outside of the grid", row, col);
+        &mut self.cells[row * self.width + col]
+    }
+}
+
+impl fmt::Display for Grid<char> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.render(|&ch| ch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Grid<char> {
+        Grid::parse_chars("abc\ndef")
+    }
+
+    #[test]
+    fn ragged_rows_are_padded() {
+        let grid = Grid::from_rows(vec![vec![1, 2, 3], vec![4], vec![]], 0);
+        assert_eq!((grid.height(), grid.width()), (3, 3));
+        assert_eq!(grid.rows(), vec![&[1, 2, 3][..], &[4, 0, 0][..], &[0, 0, 0][..]]);
+    }
+
+    #[test]
+    fn access() {
+        let mut grid = Grid::new(2, 3, 0);
+        *grid.get_mut(1, 2).unwrap() = 5;
+        grid[(0, 1)] = 7;
+        assert_eq!(grid.get(1, 2), Some(&5));
+        assert_eq!(grid[(0, 1)], 7);
+        assert_eq!(grid.get(2, 0), None);
+        assert_eq!(grid.get(0, -1), None);
+        assert!(grid.get_mut(0, 3).is_none());
+        assert_eq!(grid.positions().filter(|&pos| grid[pos] != 0).collect::<Vec<_>>(), vec![(0, 1), (1, 2)]);
+        assert_eq!(grid.map(|&value| value * 2)[(1, 2)], 10);
+    }
+
+    #[test]
+    fn columns() {
+        let grid = sample();
+        assert_eq!(grid.column(1).cloned().collect::<String>(), "be");
+        assert_eq!(grid.column(2).rev().cloned().collect::<String>(), "fc");
+    }
+
+    #[test]
+    fn transpose_and_rotations() {
+        let grid = sample();
+        assert_eq!(grid.transpose().to_string(), "ad\nbe\ncf");
+        assert_eq!(grid.rotate_clockwise().to_string(), "da\neb\nfc");
+        assert_eq!(grid.rotate_counter_clockwise().to_string(), "cf\nbe\nad");
+        assert_eq!(grid.transpose().transpose(), grid);
+        assert_eq!(grid.rotate_clockwise().rotate_counter_clockwise(), grid);
+        assert_eq!(grid.rotate_clockwise().rotate_clockwise(), grid.rotate_counter_clockwise().rotate_counter_clockwise());
+    }
+
+    #[test]
+    fn neighbours() {
+        let grid = Grid::new(3, 3, 0);
+        let mut corner = grid.neighbours4(0, 0);
+        corner.sort();
+        assert_eq!(corner, vec![(0, 1), (1, 0)]);
+        let mut corner = grid.neighbours8(0, 0);
+        corner.sort();
+        assert_eq!(corner, vec![(0, 1), (1, 0), (1, 1)]);
+        assert_eq!(grid.neighbours4(1, 1).len(), 4);
+        assert_eq!(grid.neighbours8(1, 1).len(), 8);
+        assert_eq!(grid.neighbours8(2, 1).len(), 5);
+    }
+
+    #[test]
+    fn render() {
+        let grid = Grid::from_rows(vec![vec![true, false], vec![false, true]], false);
+        assert_eq!(grid.render(|&on| if on { '#' } else { '.' }), "#.\n.#");
+    }
+
+    #[test]
+    fn parse_whitespace_separated() {
+        let grid: Grid<u32> = Grid::parse_whitespace_separated("1 2 3\n4  5\n").unwrap();
+        assert_eq!(grid.rows(), vec![&[1, 2, 3][..], &[4, 5, 0][..]]);
+
+        match Grid::<u32>::parse_whitespace_separated("1 2\n3 x") {
+            Err(Error::CouldNotParseCell(1, 1, ref s)) if s == "x" => {},
+            other => panic!("unexpected {:?}", other)
+        }
+        for input in ["", "\n", "  \n\t\n"].iter() {
+            match Grid::<u32>::parse_whitespace_separated(input) {
+                Err(Error::EmptyInput) => {},
+                other => panic!("unexpected {:?} for {:?}", other, input)
+            }
+        }
+    }
+}
@@ -1,3 +1,11 @@
+pub mod animation;
+pub mod cycle;
+pub mod grid;
+pub mod search;
+pub mod svg;
+#[cfg(test)]
+pub mod testing;
+
 use std::env;
 use std::io::prelude::*;
 use std::fs::File;
@@ -16,6 +24,14 @@ pub fn read_data(name: &str) -> String {
     contents
 }
 
+// Value following `name` in a list of command line options like `--out file.svg`
+pub fn option_value<'a>(options: &'a [String], name: &str) -> Option<&'a str> {
+    options.iter()
+        .position(|option| option == name)
+        .and_then(|idx| options.get(idx + 1))
+        .map(|value| value.as_str())
+}
+
 pub fn singleton<T: Eq + Hash>(value: T) -> HashSet<T> {
 
     let mut set = HashSet::new();
@@ -0,0 +1,208 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+#[derive(Debug, Eq, PartialEq)]
+struct QueueItem<N> {
+    node: N,
+    cost: u64,
+    estimate: u64
+}
+
+// BinaryHeap is a max-heap, so the ordering is reversed to pop the lowest estimate first
+impl<N: Eq> Ord for QueueItem<N> {
+    fn cmp(&self, other: &QueueItem<N>) -> Ordering {
+        other.estimate.cmp(&self.estimate).then_with(|| self.cost.cmp(&other.cost))
+    }
+}
+
+impl<N: Eq> PartialOrd for QueueItem<N> {
+    fn partial_cmp(&self, other: &QueueItem<N>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+pub fn reconstruct_path<N: Clone + Eq + Hash>(came_from: &HashMap<N, N>, goal: &N) -> Vec<N> {
+    let mut path = vec![goal.clone()];
+    let mut current = goal;
+    while let Some(previous) = came_from.get(current) {
+        path.push(previous.clone());
+        current = previous;
+    }
+    path.reverse();
+
+    path
+}
+
+pub fn bfs<N, F, I>(start: N, mut neighbours: F) -> HashMap<N, usize>
+    where N: Clone + Eq + Hash,
+          F: FnMut(&N) -> I,
+          I: IntoIterator<Item=N> {
+    let mut distances: HashMap<N, usize> = HashMap::new();
+    let mut queue: VecDeque<N> = VecDeque::new();
+    distances.insert(start.clone(), 0);
+    queue.push_back(start);
+
+    while let Some(current) = queue.pop_front() {
+        let distance = distances[&current];
+        for next in neighbours(&current) {
+            if !distances.contains_key(&next) {
+                distances.insert(next.clone(), distance + 1);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    distances
+}
+
+#[allow(dead_code)]
+pub fn bfs_path<N, F, I, G>(start: N, mut neighbours: F, mut is_goal: G) -> Option<Vec<N>>
+    where N: Clone + Eq + Hash,
+          F: FnMut(&N) -> I,
+          I: IntoIterator<Item=N>,
+          G: FnMut(&N) -> bool {
+    let mut came_from: HashMap<N, N> = HashMap::new();
+    let mut visited: HashSet<N> = HashSet::new();
+    let mut queue: VecDeque<N> = VecDeque::new();
+    visited.insert(start.clone());
+    queue.push_back(start);
+
+    while let Some(current) = queue.pop_front() {
+        if is_goal(&current) {
+            return Some(reconstruct_path(&came_from, &current));
+        }
+        for next in neighbours(&current) {
+            if visited.insert(next.clone()) {
+                came_from.insert(next.clone(), current.clone());
+                queue.push_back(next);
+            }
+        }
+    }
+
+    None
+}
+
+pub fn reachable<N, F, I>(start: N, neighbours: F) -> HashSet<N>
+    where N: Clone + Eq + Hash,
+          F: FnMut(&N) -> I,
+          I: IntoIterator<Item=N> {
+    bfs(start, neighbours).into_keys().collect()
+}
+
+pub fn astar<N, F, I, H, G>(start: N, mut neighbours: F, mut heuristic: H, mut is_goal: G) -> Option<(Vec<N>, u64)>
+    where N: Clone + Eq + Hash,
+          F: FnMut(&N) -> I,
+          I: IntoIterator<Item=(N, u64)>,
+          H: FnMut(&N) -> u64,
+          G: FnMut(&N) -> bool {
+    let mut came_from: HashMap<N, N> = HashMap::new();
+    let mut best_costs: HashMap<N, u64> = HashMap::new();
+    let mut queue: BinaryHeap<QueueItem<N>> = BinaryHeap::new();
+
+    best_costs.insert(start.clone(), 0);
+    queue.push(QueueItem { estimate: heuristic(&start), node: start, cost: 0 });
+
+    while let Some(QueueItem { node, cost, .. }) = queue.pop() {
+        if is_goal(&node) {
+            return Some((reconstruct_path(&came_from, &node), cost));
+        }
+        if cost > best_costs[&node] {
+            continue;
+        }
+
+        for (next, step_cost) in neighbours(&node) {
+            let next_cost = cost + step_cost;
+            let improved = best_costs.get(&next).is_none_or(|&known| next_cost < known);
+            if improved {
+                best_costs.insert(next.clone(), next_cost);
+                came_from.insert(next.clone(), node.clone());
+                queue.push(QueueItem { estimate: next_cost + heuristic(&next), node: next, cost: next_cost });
+            }
+        }
+    }
+
+    None
+}
+
+#[allow(dead_code)]
+pub fn dijkstra<N, F, I, G>(start: N, neighbours: F, is_goal: G) -> Option<(Vec<N>, u64)>
+    where N: Clone + Eq + Hash,
+          F: FnMut(&N) -> I,
+          I: IntoIterator<Item=(N, u64)>,
+          G: FnMut(&N) -> bool {
+    astar(start, neighbours, |_| 0, is_goal)
+}
+
+pub fn connected_components<N, F, I, J>(nodes: J, mut neighbours: F) -> Vec<HashSet<N>>
+    where N: Clone + Eq + Hash,
+          F: FnMut(&N) -> I,
+          I: IntoIterator<Item=N>,
+          J: IntoIterator<Item=N> {
+    let mut visited: HashSet<N> = HashSet::new();
+    let mut components: Vec<HashSet<N>> = Vec::new();
+
+    for node in nodes {
+        if !visited.contains(&node) {
+            let component = reachable(node, &mut neighbours);
+            visited.extend(component.iter().cloned());
+            components.push(component);
+        }
+    }
+
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Integers below 20, connected by +1 and *2
+    fn steps(n: &u32) -> Vec<u32> {
+        vec![n + 1, n * 2].into_iter().filter(|&next| next < 20).collect()
+    }
+
+    // 0 -> 1 -> 2 costs 2 in total, 0 -> 2 directly costs 5
+    fn weighted(n: &u32) -> Vec<(u32, u64)> {
+        match *n {
+            0 => vec![(1, 1), (2, 5)],
+            1 => vec![(2, 1)],
+            2 => vec![(3, 1)],
+            _ => vec![]
+        }
+    }
+
+    #[test]
+    fn bfs_distances() {
+        let distances = bfs(1, steps);
+        assert_eq!(distances.len(), 19);
+        assert_eq!(distances[&1], 0);
+        assert_eq!(distances[&2], 1);
+        assert_eq!(distances[&16], 4);
+        assert_eq!(distances[&19], 6);
+    }
+
+    #[test]
+    fn bfs_shortest_path() {
+        assert_eq!(bfs_path(1, steps, |&n| n == 12), Some(vec![1, 2, 3, 6, 12]));
+        assert_eq!(bfs_path(5, steps, |&n| n == 5), Some(vec![5]));
+        assert_eq!(bfs_path(5, steps, |&n| n == 3), None);
+    }
+
+    #[test]
+    fn reachable_and_components() {
+        assert_eq!(reachable(15, steps), (15..20).collect());
+
+        let pairs = |n: &u32| vec![n ^ 1];
+        let mut sizes: Vec<usize> = connected_components(0..6, pairs).iter().map(|component| component.len()).collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![2, 2, 2]);
+    }
+
+    #[test]
+    fn weighted_paths() {
+        assert_eq!(dijkstra(0, weighted, |&n| n == 3), Some((vec![0, 1, 2, 3], 3)));
+        assert_eq!(dijkstra(3, weighted, |&n| n == 0), None);
+        assert_eq!(astar(0, weighted, |&n| 3 - n as u64, |&n| n == 3), Some((vec![0, 1, 2, 3], 3)));
+    }
+}
@@ -0,0 +1,137 @@
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+
+#[derive(Clone, Debug)]
+pub struct Style {
+    fill: String,
+    stroke: String,
+    stroke_width: f64
+}
+
+impl Style {
+    pub fn fill(color: &str) -> Self {
+        Style { fill: String::from(color), stroke: String::from("none"), stroke_width: 0.0 }
+    }
+
+    pub fn stroke(color: &str, width: f64) -> Self {
+        Style { fill: String::from("none"), stroke: String::from(color), stroke_width: width }
+    }
+
+    pub fn with_stroke(self, color: &str, width: f64) -> Self {
+        Style { stroke: String::from(color), stroke_width: width, ..self }
+    }
+
+    fn attributes(&self) -> String {
+        format!(r#"fill="{}" stroke="{}" stroke-width="{}""#, self.fill, self.stroke, self.stroke_width)
+    }
+}
+
+pub struct Document {
+    width: f64,
+    height: f64,
+    view_box: (f64, f64, f64, f64),
+    elements: Vec<String>
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Hue spread by the golden angle so that neighbouring indices get clearly different colors
+pub fn palette_color(idx: usize) -> String {
+    format!("hsl({}, 70%, 50%)", (idx * 137) % 360)
+}
+
+impl Document {
+    pub fn new(width: f64, height: f64) -> Self {
+        Document {
+            width,
+            height,
+            view_box: (0.0, 0.0, width, height),
+            elements: Vec::new()
+        }
+    }
+
+    // Keeps the output size but maps the given world rectangle onto it
+    pub fn with_view_box(self, min_x: f64, min_y: f64, width: f64, height: f64) -> Self {
+        Document { view_box: (min_x, min_y, width, height), ..self }
+    }
+
+    pub fn rect(&mut self, x: f64, y: f64, width: f64, height: f64, style: &Style) {
+        self.elements.push(format!(r#"<rect x="{}" y="{}" width="{}" height="{}" {}/>"#, x, y, width, height, style.attributes()));
+    }
+
+    pub fn circle(&mut self, cx: f64, cy: f64, r: f64, style: &Style) {
+        self.elements.push(format!(r#"<circle cx="{}" cy="{}" r="{}" {}/>"#, cx, cy, r, style.attributes()));
+    }
+
+    #[allow(dead_code)]
+    pub fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64, style: &Style) {
+        self.elements.push(format!(r#"<line x1="{}" y1="{}" x2="{}" y2="{}" {}/>"#, x1, y1, x2, y2, style.attributes()));
+    }
+
+    pub fn polyline(&mut self, points: &[(f64, f64)], style: &Style) {
+        let point_strs: Vec<String> = points.iter().map(|&(x, y)| format!("{},{}", x, y)).collect();
+        self.elements.push(format!(r#"<polyline points="{}" {}/>"#, point_strs.join(" "), style.attributes()));
+    }
+
+    pub fn text(&mut self, x: f64, y: f64, size: f64, content: &str, color: &str) {
+        self.elements.push(format!(
+            r#"<text x="{}" y="{}" font-family="monospace" font-size="{}" text-anchor="middle" dominant-baseline="central" fill="{}">{}</text>"#,
+            x, y, size, color, escape(content)));
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(self.to_string().as_bytes())
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (min_x, min_y, vb_width, vb_height) = self.view_box;
+        writeln!(f, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="{} {} {} {}">"#,
+                 self.width, self.height, min_x, min_y, vb_width, vb_height)?;
+        for element in self.elements.iter() {
+            writeln!(f, "  {}", element)?;
+        }
+        writeln!(f, "</svg>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn document() {
+        let mut doc = Document::new(100.0, 50.0).with_view_box(-1.0, -1.0, 2.0, 1.0);
+        doc.rect(0.0, 0.0, 1.0, 0.5, &Style::fill("red").with_stroke("black", 0.1));
+        doc.circle(0.5, 0.5, 0.25, &Style::fill("blue"));
+        doc.line(0.0, 0.0, 1.0, 1.0, &Style::stroke("green", 0.05));
+        doc.polyline(&[(0.0, 0.0), (1.0, 0.5)], &Style::stroke("gray", 0.02));
+        doc.text(0.5, 0.5, 0.2, "a<b & \"c\"", "white");
+
+        let lines: Vec<String> = doc.to_string().lines().map(String::from).collect();
+        assert_eq!(lines, vec![
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50" viewBox="-1 -1 2 1">"#,
+            r#"  <rect x="0" y="0" width="1" height="0.5" fill="red" stroke="black" stroke-width="0.1"/>"#,
+            r#"  <circle cx="0.5" cy="0.5" r="0.25" fill="blue" stroke="none" stroke-width="0"/>"#,
+            r#"  <line x1="0" y1="0" x2="1" y2="1" fill="none" stroke="green" stroke-width="0.05"/>"#,
+            r#"  <polyline points="0,0 1,0.5" fill="none" stroke="gray" stroke-width="0.02"/>"#,
+            r#"  <text x="0.5" y="0.5" font-family="monospace" font-size="0.2" text-anchor="middle" dominant-baseline="central" fill="white">a&lt;b &amp; &quot;c&quot;</text>"#,
+            "</svg>"
+        ]);
+    }
+
+    #[test]
+    fn palette() {
+        assert_eq!(palette_color(0), "hsl(0, 70%, 50%)");
+        assert_eq!(palette_color(3), "hsl(51, 70%, 50%)");
+    }
+}
@@ -0,0 +1,20 @@
+// Deterministic pseudo-random numbers for the tests (Knuth's MMIX linear congruential generator)
+pub struct Lcg {
+    state: u64
+}
+
+impl Lcg {
+    pub fn new(seed: u64) -> Self {
+        Lcg { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        self.state
+    }
+
+    // Uses the high bits, which have a much longer period than the low ones
+    pub fn below(&mut self, bound: u64) -> u64 {
+        (self.next_u64() >> 33) % bound
+    }
+}
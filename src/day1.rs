@@ -1,3 +1,88 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidDigit(usize, char),
+    InvalidByte(usize, u8),
+    InvalidSymbols(Vec<(usize, char)>),
+    InvalidRadix(u32),
+    InputTooLong(usize),
+    CouldNotRead(io::Error)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidDigit(offset, ch) => write!(f, "invalid digit {:?} at byte offset {}", ch, offset),
+            Error::InvalidByte(offset, byte) => write!(f, "invalid byte 0x{:02x} at byte offset {}", byte, offset),
+            Error::InvalidSymbols(ref symbols) => {
+                let positions: Vec<String> = symbols.iter().map(|&(idx, ch)| format!("{:?} at {}", ch, idx)).collect();
+                write!(f, "invalid symbols: {}", positions.join(", "))
+            },
+            Error::InvalidRadix(radix) => write!(f, "radix {} is not between 2 and 36", radix),
+            Error::InputTooLong(len) => write!(f, "input of {} digits is longer than the {} supported", len, MAX_PROFILE_LEN),
+            Error::CouldNotRead(ref err) => write!(f, "could not read the input: {}", err)
+        }
+    }
+}
+
+// Only built through `radix` and `symbols`, so a radix is always between 2 and 36
+pub struct Alphabet {
+    kind: AlphabetKind
+}
+
+enum AlphabetKind {
+    Radix(u32),
+    Symbols(HashMap<char, u64>)
+}
+
+impl Alphabet {
+    pub fn radix(radix: u32) -> Result<Alphabet, Error> {
+        if (2..37).contains(&radix) {
+            Ok(Alphabet { kind: AlphabetKind::Radix(radix) })
+        } else {
+            Err(Error::InvalidRadix(radix))
+        }
+    }
+
+    pub fn symbols(table: &[(char, u64)]) -> Alphabet {
+        Alphabet { kind: AlphabetKind::Symbols(table.iter().cloned().collect()) }
+    }
+
+    fn weight(&self, ch: char) -> Option<u64> {
+        match self.kind {
+            AlphabetKind::Radix(radix) => ch.to_digit(radix).map(|d| d as u64),
+            AlphabetKind::Symbols(ref table) => table.get(&ch).cloned()
+        }
+    }
+}
+
+// Same as `compute`, but symbols are matched and summed by their weight in the given alphabet.
+// Symbol weights can be any u64, so the sum is an u128
+pub fn compute_with(input: &str, shift: usize, alphabet: &Alphabet) -> Result<u128, Error> {
+    let mut weights: Vec<u64> = Vec::new();
+    let mut invalid: Vec<(usize, char)> = Vec::new();
+    for (idx, ch) in input.chars().enumerate() {
+        match alphabet.weight(ch) {
+            Some(weight) => weights.push(weight),
+            None => invalid.push((idx, ch))
+        }
+    }
+
+    if !invalid.is_empty() {
+        Err(Error::InvalidSymbols(invalid))
+    } else if weights.is_empty() {
+        Ok(0)
+    } else {
+        let n = weights.len();
+        Ok((0..n).filter(|&i| weights[i] == weights[(i + shift) % n]).map(|i| weights[i] as u128).sum())
+    }
+}
+
 fn compute(input: &str, shift: usize) -> u32 {
     let chars1: Vec<char> = input.chars().collect();
     let chars2: Vec<char> = input.chars().skip(shift).chain(input.chars().take(shift)).collect();
@@ -7,9 +92,270 @@ fn compute(input: &str, shift: usize) -> u32 {
     pairs.map(|(a, b)| if a == b { a.to_digit(10).unwrap() } else { 0 }).sum()
 }
 
+// Reads the digits one by one, keeping only the last `shift` digits and the first `shift` digits
+// for the wrap-around comparisons. Whitespace is skipped, any other non-digit byte is rejected.
+pub fn compute_streaming<R: Read>(input: R, shift: usize) -> Result<u64, Error> {
+    let mut first: Vec<u8> = Vec::with_capacity(shift);
+    let mut ring: Vec<u8> = Vec::with_capacity(shift);
+    let mut count: usize = 0;
+    let mut sum: u64 = 0;
+
+    for (offset, byte) in BufReader::new(input).bytes().enumerate() {
+        let byte = byte.map_err(Error::CouldNotRead)?;
+        if byte.is_ascii_whitespace() {
+            continue;
+        }
+        // Only ASCII bytes are characters on their own, anything else is part of a multi-byte sequence
+        if !byte.is_ascii() {
+            return Err(Error::InvalidByte(offset, byte));
+        }
+        let ch = byte as char;
+        let digit = ch.to_digit(10).ok_or(Error::InvalidDigit(offset, ch))? as u8;
+
+        if shift == 0 {
+            sum += digit as u64;
+        } else if ring.len() < shift {
+            first.push(digit);
+            ring.push(digit);
+        } else {
+            let slot = count % shift;
+            if ring[slot] == digit {
+                sum += digit as u64;
+            }
+            ring[slot] = digit;
+        }
+        count += 1;
+    }
+
+    if shift > 0 && count > 0 {
+        if count <= shift {
+            // Everything fits in the first digits, so the shift simply wraps around the whole input
+            let effective_shift = shift % count;
+            for i in 0..count {
+                if first[i] == first[(i + effective_shift) % count] {
+                    sum += first[i] as u64;
+                }
+            }
+        } else {
+            for (k, &head) in first.iter().enumerate() {
+                let tail = ring[(count - shift + k) % shift];
+                if tail == head {
+                    sum += tail as u64;
+                }
+            }
+        }
+    }
+
+    Ok(sum)
+}
+
+// 998244353 = 119 * 2^23 + 1, so it only has roots of unity for transform sizes up to 2^23
+const MODULUS: u64 = 998244353;
+const PRIMITIVE_ROOT: u64 = 3;
+const MAX_TRANSFORM_SIZE: usize = 1 << 23;
+// The correlation needs a transform of at least twice the input length
+const MAX_PROFILE_LEN: usize = MAX_TRANSFORM_SIZE / 2;
+
+fn pow_mod(base: u64, exp: u64) -> u64 {
+    let mut result = 1;
+    let mut base = base % MODULUS;
+    let mut exp = exp;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result * base % MODULUS;
+        }
+        base = base * base % MODULUS;
+        exp >>= 1;
+    }
+
+    result
+}
+
+// In-place iterative number theoretic transform, values.len() must be a power of two
+fn ntt(values: &mut [u64], invert: bool) {
+    let n = values.len();
+
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j ^= bit;
+        if i < j {
+            values.swap(i, j);
+        }
+    }
+
+    let mut len = 2;
+    while len <= n {
+        let root = pow_mod(PRIMITIVE_ROOT, (MODULUS - 1) / len as u64);
+        let root = if invert { pow_mod(root, MODULUS - 2) } else { root };
+        for start in (0..n).step_by(len) {
+            let mut w = 1;
+            for k in 0..len / 2 {
+                let u = values[start + k];
+                let v = values[start + k + len / 2] * w % MODULUS;
+                values[start + k] = (u + v) % MODULUS;
+                values[start + k + len / 2] = (u + MODULUS - v) % MODULUS;
+                w = w * root % MODULUS;
+            }
+        }
+        len <<= 1;
+    }
+
+    if invert {
+        let n_inv = pow_mod(n as u64, MODULUS - 2);
+        for value in values.iter_mut() {
+            *value = *value * n_inv % MODULUS;
+        }
+    }
+}
+
+// Sum of matching digits for every shift 0..n, via the cyclic autocorrelation of each digit's indicator sequence.
+// Inputs are limited to MAX_PROFILE_LEN (2^22) digits by the transform modulus.
+pub fn shift_profile(input: &str) -> Result<Vec<u64>, Error> {
+    let mut digits: Vec<u32> = Vec::with_capacity(input.len());
+    for (offset, ch) in input.char_indices() {
+        digits.push(ch.to_digit(10).ok_or(Error::InvalidDigit(offset, ch))?);
+    }
+    let n = digits.len();
+    if n == 0 {
+        return Ok(vec![]);
+    }
+    if n > MAX_PROFILE_LEN {
+        return Err(Error::InputTooLong(n));
+    }
+    let size = (2 * n).next_power_of_two();
+
+    let mut total = vec![0u64; size];
+    for digit in 1..10 {
+        let mut forward = vec![0u64; size];
+        let mut backward = vec![0u64; size];
+        for (i, &d) in digits.iter().enumerate() {
+            if d == digit {
+                forward[i] = 1;
+                backward[n - 1 - i] = 1;
+            }
+        }
+        ntt(&mut forward, false);
+        ntt(&mut backward, false);
+        for i in 0..size {
+            total[i] = (total[i] + forward[i] * backward[i] % MODULUS * digit as u64) % MODULUS;
+        }
+    }
+    ntt(&mut total, true);
+
+    // Linear correlation at offset t is at index n - 1 - t, a cyclic shift s wraps around as s and s - n
+    Ok((0..n).map(|shift| total[n - 1 - shift] + total[2 * n - 1 - shift]).collect())
+}
+
+// Same as `shift_profile`, but runs `compute` for every shift in O(n^2) and has no length limit.
+// Kept to cross-check the transform
+#[allow(dead_code)]
+pub fn shift_profile_naive(input: &str) -> Result<Vec<u64>, Error> {
+    if let Some((offset, ch)) = input.char_indices().find(|&(_, ch)| !ch.is_ascii_digit()) {
+        return Err(Error::InvalidDigit(offset, ch));
+    }
+
+    Ok((0..input.len()).map(|shift| compute(input, shift) as u64).collect())
+}
+
+const INPUT: &str = "9513446799636685297929646689682997114316733445451534532351778534251427172168183621874641711534917291674333857423799375512628489423332297538215855176592633692631974822259161766238385922277893623911332569448978771948316155868781496698895492971356383996932885518732997624253678694279666572149831616312497994856288871586777793459926952491318336997159553714584541897294117487641872629796825583725975692264125865827534677223541484795877371955124463989228886498682421539667224963783616245646832154384756663251487668681425754536722827563651327524674183443696227523828832466473538347472991998913211857749878157579176457395375632995576569388455888156465451723693767887681392547189273391948632726499868313747261828186732986628365773728583387184112323696592536446536231376615949825166773536471531487969852535699774113163667286537193767515119362865141925612849443983484245268194842563154567638354645735331855896155142741664246715666899824364722914296492444672653852387389477634257768229772399416521198625393426443499223611843766134883441223328256883497423324753229392393974622181429913535973327323952241674979677481518733692544535323219895684629719868384266425386835539719237716339198485163916562434854579365958111931354576991558771236977242668756782139961638347251644828724786827751748399123668854393894787851872256667336215726674348886747128237416273154988619267824361227888751562445622387695218161341884756795223464751862965655559143779425283154533252573949165492138175581615176611845489857169132936848668646319955661492488428427435269169173654812114842568381636982389224236455633316898178163297452453296667661849622174541778669494388167451186352488555379581934999276412919598411422973399319799937518713422398874326665375216437246445791623283898584648278989674418242112957668397484671119761553847275799873495363759266296477844157237423239163559391553961176475377151369399646747881452252547741718734949967752564774161341784833521492494243662658471121369649641815562327698395293573991648351369767162642763475561544795982183714447737149239846151871434656618825566387329765118727515699213962477996399781652131918996434125559698427945714572488376342126989157872118279163127742349";
+
 pub fn run() {
-    let input = "9513446799636685297929646689682997114316733445451534532351778534251427172168183621874641711534917291674333857423799375512628489423332297538215855176592633692631974822259161766238385922277893623911332569448978771948316155868781496698895492971356383996932885518732997624253678694279666572149831616312497994856288871586777793459926952491318336997159553714584541897294117487641872629796825583725975692264125865827534677223541484795877371955124463989228886498682421539667224963783616245646832154384756663251487668681425754536722827563651327524674183443696227523828832466473538347472991998913211857749878157579176457395375632995576569388455888156465451723693767887681392547189273391948632726499868313747261828186732986628365773728583387184112323696592536446536231376615949825166773536471531487969852535699774113163667286537193767515119362865141925612849443983484245268194842563154567638354645735331855896155142741664246715666899824364722914296492444672653852387389477634257768229772399416521198625393426443499223611843766134883441223328256883497423324753229392393974622181429913535973327323952241674979677481518733692544535323219895684629719868384266425386835539719237716339198485163916562434854579365958111931354576991558771236977242668756782139961638347251644828724786827751748399123668854393894787851872256667336215726674348886747128237416273154988619267824361227888751562445622387695218161341884756795223464751862965655559143779425283154533252573949165492138175581615176611845489857169132936848668646319955661492488428427435269169173654812114842568381636982389224236455633316898178163297452453296667661849622174541778669494388167451186352488555379581934999276412919598411422973399319799937518713422398874326665375216437246445791623283898584648278989674418242112957668397484671119761553847275799873495363759266296477844157237423239163559391553961176475377151369399646747881452252547741718734949967752564774161341784833521492494243662658471121369649641815562327698395293573991648351369767162642763475561544795982183714447737149239846151871434656618825566387329765118727515699213962477996399781652131918996434125559698427945714572488376342126989157872118279163127742349";
+    let input = INPUT;
 
     println!("Day 1 first result is: {}", compute(input, 1));
     println!("Day 1 second result is: {}", compute(input, input.len() / 2));
+
+    match compute_streaming("1122\n12x4".as_bytes(), 1) {
+        Ok(sum) => println!("Day 1 streaming example: {}", sum),
+        Err(err) => println!("Day 1 streaming example error: {}", err)
+    }
+
+    println!("Day 1 hex example: {}", compute_with("9aA0fF", 1, &Alphabet::radix(16).unwrap()).unwrap());
+    println!("Day 1 custom alphabet example: {}", compute_with("xyyzx", 1, &Alphabet::symbols(&[('x', 1), ('y', 5), ('z', 10)])).unwrap());
+    match compute_with("12g4h", 1, &Alphabet::radix(16).unwrap()) {
+        Ok(sum) => println!("Day 1 hex example: {}", sum),
+        Err(err) => println!("Day 1 hex example error: {}", err)
+    }
+
+    let profile = shift_profile(input).unwrap();
+    let (best_shift, best_sum) = profile.iter().enumerate().skip(1).max_by_key(|&(_, sum)| sum).unwrap();
+    println!("Day 1 shift profile: shift 1 = {}, shift {} = {}, best shift {} = {}",
+             profile[1], input.len() / 2, profile[input.len() / 2], best_shift, best_sum);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::testing::Lcg;
+
+    #[test]
+    fn streaming_matches_compute() {
+        for &shift in [1, INPUT.len() / 2].iter() {
+            assert_eq!(compute_streaming(INPUT.as_bytes(), shift).unwrap(), compute(INPUT, shift) as u64);
+        }
+    }
+
+    #[test]
+    fn streaming_reports_invalid_bytes() {
+        match compute_streaming("12x4".as_bytes(), 1) {
+            Err(Error::InvalidDigit(2, 'x')) => {},
+            other => panic!("unexpected {:?}", other)
+        }
+        // 'é' is 0xc3 0xa9 in UTF-8
+        match compute_streaming("1\n\u{e9}2".as_bytes(), 1) {
+            Err(Error::InvalidByte(2, 0xc3)) => {},
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn decimal_alphabet_matches_compute() {
+        assert_eq!(compute_with(INPUT, 1, &Alphabet::radix(10).unwrap()).unwrap(), compute(INPUT, 1) as u128);
+    }
+
+    #[test]
+    fn alphabets() {
+        for &radix in [0, 1, 37, u32::MAX].iter() {
+            match Alphabet::radix(radix) {
+                Err(Error::InvalidRadix(invalid)) => assert_eq!(invalid, radix),
+                _ => panic!("radix {} accepted", radix)
+            }
+        }
+        assert_eq!(compute_with("zZz", 1, &Alphabet::radix(36).unwrap()).unwrap(), 35 * 3);
+
+        let heavy = Alphabet::symbols(&[('x', u64::MAX), ('y', 1)]);
+        assert_eq!(compute_with("xxxy", 1, &heavy).unwrap(), 2 * u64::MAX as u128);
+    }
+
+    #[test]
+    fn shift_profile_matches_naive() {
+        assert_eq!(shift_profile(INPUT).unwrap(), shift_profile_naive(INPUT).unwrap());
+
+        let mut rng = Lcg::new(7);
+        for &len in [1, 2, 3, 17, 64, 100].iter() {
+            let input: String = (0..len).map(|_| (b'0' + rng.below(10) as u8) as char).collect();
+            assert_eq!(shift_profile(&input).unwrap(), shift_profile_naive(&input).unwrap(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn shift_profile_rejects_invalid_input() {
+        for profile in [shift_profile("12x4"), shift_profile_naive("12x4")].iter() {
+            match *profile {
+                Err(Error::InvalidDigit(2, 'x')) => {},
+                ref other => panic!("unexpected {:?}", other)
+            }
+        }
+
+        let too_long = "1".repeat(MAX_PROFILE_LEN + 1);
+        match shift_profile(&too_long) {
+            Err(Error::InputTooLong(len)) => assert_eq!(len, MAX_PROFILE_LEN + 1),
+            other => panic!("unexpected {:?}", other.map(|profile| profile.len()))
+        }
+    }
 }
@@ -1,6 +1,7 @@
 use common;
+use common::search;
+use common::svg;
 use std::cmp;
-use std::collections::{BinaryHeap, HashSet};
 
 #[derive(Debug)]
 enum Error {
@@ -65,45 +66,14 @@ impl Coords {
         self.y = self.y + dy;
     }
 
-    fn dist2(&self, other: &Coords) -> i64 {
-        let dx: i64 = (other.x - self.x) as i64;
-        let dy: i64 = (other.y - self.y) as i64;
-        dx*dx + dy*dy
-    }
-}
-
-#[derive(Debug, Eq)]
-struct SearchStep {
-    state: Coords,
-    history: u32,
-    cost: i64
-}
-
-impl PartialEq for SearchStep {
-    fn eq(&self, other: &SearchStep) -> bool {
-        self.state == other.state && self.history == other.history && self.cost == other.cost
-    }
-}
-
-impl Ord for SearchStep {
-    fn cmp(&self, other: &SearchStep) -> cmp::Ordering {
-        self.cost.partial_cmp(&other.cost).map(|o| o.reverse()).unwrap()
-    }
-}
-
-impl PartialOrd for SearchStep {
-    fn partial_cmp(&self, other: &SearchStep) -> Option<cmp::Ordering> {
-        Some(self.cost.partial_cmp(&other.cost).map(|o| o.reverse()).unwrap())
-    }
-}
-
-impl SearchStep {
-    pub fn next(&self, step: &Step, goal: &Coords) -> Self {
-        let next_state = self.state.step(step);
-        SearchStep {
-            state: next_state,
-            history: self.history + 1,
-            cost: next_state.dist2(goal)
+    // Lower bound of the steps needed on the hex grid, used as the A* heuristic
+    fn hex_distance(&self, other: &Coords) -> u64 {
+        let dx = u64::from((other.x - self.x).unsigned_abs());
+        let dy = u64::from((other.y - self.y).unsigned_abs());
+        if dy > dx {
+            dx + (dy - dx) / 2
+        } else {
+            dx
         }
     }
 }
@@ -113,33 +83,49 @@ fn valid_steps(_coords: &Coords) -> Vec<Step> {
 }
 
 fn find_path(start: &Coords, goal: &Coords) -> u32 {
-    let mut search_steps: BinaryHeap<SearchStep> = BinaryHeap::new();
-    let mut visited: HashSet<Coords> = HashSet::new();
-    let mut result = None;
+    let (_path, cost) = search::astar(
+        *start,
+        |coords| valid_steps(coords).iter().map(|step| (coords.step(step), 1)).collect::<Vec<(Coords, u64)>>(),
+        |coords| coords.hex_distance(goal),
+        |coords| coords == goal).unwrap();
 
-    search_steps.push(SearchStep {
-        state: start.clone(),
-        history: 0,
-        cost: 0
-    });
+    cost as u32
+}
 
-    while let Some(step) = search_steps.pop() {
+fn screen_position(coords: &Coords) -> (f64, f64) {
+    (coords.x as f64 * 1.5, coords.y as f64 * 3.0f64.sqrt() / 2.0)
+}
 
-        if step.state == *goal {
-            result = Some(step);
-            search_steps.clear();
-        } else {
-            for next_step in valid_steps(&step.state) {
-                let next_coords = step.state.step(&next_step);
-                if !visited.contains(&next_coords) {
-                    search_steps.push(step.next(&next_step, goal));
-                    visited.insert(next_coords);
-                }
-            }
-        }
+pub fn render() -> svg::Document {
+    let input = common::read_data("day11.txt");
+    let steps: Vec<Step> = input.split(",").map(|s| Step::from_string(s).unwrap()).collect();
+    let start = Coords { x: 0, y: 0 };
+
+    let mut path: Vec<Coords> = vec![start];
+    for step in steps.iter() {
+        let next = path[path.len() - 1].step(step);
+        path.push(next);
+    }
+    let furthest = *path.iter().max_by_key(|coords| coords.hex_distance(&start)).unwrap();
+    let end = path[path.len() - 1];
+
+    let points: Vec<(f64, f64)> = path.iter().map(screen_position).collect();
+    let min_x = points.iter().map(|p| p.0).fold(0.0, f64::min) - 10.0;
+    let max_x = points.iter().map(|p| p.0).fold(0.0, f64::max) + 10.0;
+    let min_y = points.iter().map(|p| p.1).fold(0.0, f64::min) - 10.0;
+    let max_y = points.iter().map(|p| p.1).fold(0.0, f64::max) + 10.0;
+
+    let mut doc = svg::Document::new(800.0, 800.0 * (max_y - min_y) / (max_x - min_x))
+        .with_view_box(min_x, min_y, max_x - min_x, max_y - min_y);
+    doc.rect(min_x, min_y, max_x - min_x, max_y - min_y, &svg::Style::fill("white"));
+    doc.polyline(&points, &svg::Style::stroke("steelblue", 0.5));
+
+    for &(coords, color) in [(start, "green"), (furthest, "orange"), (end, "red")].iter() {
+        let (x, y) = screen_position(&coords);
+        doc.circle(x, y, 3.0, &svg::Style::fill(color));
     }
 
-    result.unwrap().history
+    doc
 }
 
 pub fn run() {
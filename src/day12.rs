@@ -1,7 +1,7 @@
 use common;
+use common::search;
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::collections::VecDeque;
 
 #[derive(Copy, Clone, Debug, Eq, Hash)]
 struct Program {
@@ -26,20 +26,7 @@ fn parse_line(line: &str) -> (Program, HashSet<Program>) {
 }
 
 fn get_accessible_programs(connections: &HashMap<Program, HashSet<Program>>, from: Program) -> HashSet<Program> {
-    let mut visited: HashSet<Program> = HashSet::new();
-    let mut queue: VecDeque<Program> = VecDeque::new();
-    queue.push_back(from);
-
-    while let Some(current) = queue.pop_front() {
-        for target in connections.get(&current).unwrap().iter() {
-            if !visited.contains(&target) {
-                queue.push_back(target.clone());
-            }
-        }
-        visited.insert(current);
-    }
-
-    visited
+    search::reachable(from, |current| connections.get(current).unwrap().clone())
 }
 
 fn count_accessible_programs(connections: &HashMap<Program, HashSet<Program>>) -> usize {
@@ -47,20 +34,7 @@ fn count_accessible_programs(connections: &HashMap<Program, HashSet<Program>>) -
 }
 
 fn count_components(connections: &HashMap<Program, HashSet<Program>>) -> usize {
-    let mut component_count: usize = 0;
-    let mut visited: HashSet<Program> = HashSet::new();
-
-    for program in connections.keys() {
-        if !visited.contains(program) {
-            let component = get_accessible_programs(connections, *program);
-            for p in component {
-                visited.insert(p);
-            }
-            component_count = component_count + 1;
-        }
-    }
-
-    component_count
+    search::connected_components(connections.keys().cloned(), |current| connections.get(current).unwrap().clone()).len()
 }
 
 pub fn run() {
@@ -1,4 +1,7 @@
 use common;
+use common::animation;
+use common::animation::Animation;
+use common::svg;
 
 struct LayerSpec {
     idx: usize,
@@ -119,44 +122,91 @@ impl Simulation {
         }
     }
 
-    #[allow(dead_code)]
-    fn dump(&self) {
+    fn draw(&self) -> String {
+        let mut result = String::new();
         let max_range = self.layers.iter().map(|layer| layer.range).max().unwrap();
         for r in 0..(max_range + 1) {
             for i in 0..self.layers.len() {
                 if r == 0 {
-                    print!(" {}  ", i);
+                    result.push_str(&format!(" {}  ", i));
                 } else {
                     let is_current = self.position == i && r == 1;
                     if self.layers[i].range >= r {
                         if self.layers[i].state == (r - 1) {
                             if is_current {
-                            print!("(S) ");
+                                result.push_str("(S) ");
                             } else {
-                                print!("[S] ");
+                                result.push_str("[S] ");
                             }
                         } else {
                             if is_current {
-                                print!("( ) ");
+                                result.push_str("( ) ");
                             } else {
-                                print!("[ ] ");
+                                result.push_str("[ ] ");
                             }
 
                         }
                     } else {
                         if is_current {
-                            print!("(.) ");
+                            result.push_str("(.) ");
                         } else {
-                            print!("... ");
+                            result.push_str("... ");
                         }
                     }
                 }
             }
-            println!();
+            result.push('\n');
+        }
+        result.push_str(&format!("=> {}, {}\n", self.caught, self.total_severity));
+        result
+    }
+
+    fn dump(&self) {
+        println!("{}", self.draw());
+    }
+}
+
+impl Animation for Simulation {
+    fn frame(&self) -> String {
+        self.draw()
+    }
+
+    fn advance(&mut self) -> bool {
+        self.step()
+    }
+}
+
+pub fn animate(fps: f64) {
+    let input = common::read_data("day13.txt");
+    let layer_specs: Vec<LayerSpec> = input.split("\n").map(LayerSpec::parse).collect();
+    animation::play(&mut Simulation::init(&layer_specs), fps);
+}
+
+pub fn render(tick: usize) -> svg::Document {
+    const CELL: f64 = 20.0;
+    let input = common::read_data("day13.txt");
+    let layer_specs: Vec<LayerSpec> = input.split("\n").map(LayerSpec::parse).collect();
+    let mut simulation = Simulation::init(&layer_specs);
+    simulation.delay(tick as i32);
+
+    let max_range = simulation.layers.iter().map(|layer| layer.range).max().unwrap();
+    let width = simulation.layers.len() as f64 * CELL;
+    let height = (max_range + 1) as f64 * CELL;
+    let mut doc = svg::Document::new(width, height);
+    doc.rect(0.0, 0.0, width, height, &svg::Style::fill("white"));
+
+    let empty = svg::Style::fill("white").with_stroke("gray", 1.0);
+    let scanner = svg::Style::fill("crimson").with_stroke("gray", 1.0);
+    for (idx, layer) in simulation.layers.iter().enumerate() {
+        let x = idx as f64 * CELL;
+        doc.text(x + CELL / 2.0, CELL / 2.0, CELL / 2.0, &idx.to_string(), "black");
+        for r in 0..layer.range {
+            let style = if layer.state == r { &scanner } else { &empty };
+            doc.rect(x + 2.0, (r + 1) as f64 * CELL + 2.0, CELL - 4.0, CELL - 4.0, style);
         }
-        println!("=> {}, {}", self.caught, self.total_severity);
-        println!();
     }
+
+    doc
 }
 
 fn part1(name: &str, layer_specs: &Vec<LayerSpec>, dump: bool) {
@@ -1,7 +1,10 @@
-extern crate multiarray;
-
-use self::multiarray::*;
+use common::grid::Grid;
+use common::search;
+use common::svg;
 use day10::knot_hash;
+use std::collections::HashSet;
+
+const INPUT: &str = "hxtvlmkl";
 
 fn row_map(row: u8, hash: &str) -> String {
     let hash = knot_hash(format!("{}-{}", hash, row).as_str());
@@ -9,52 +12,43 @@ fn row_map(row: u8, hash: &str) -> String {
     parts.as_slice().join("")
 }
 
-fn usage_map(hash: &str) -> Array2D<bool> {
-    let mut result: Array2D<bool> = Array2D::new([128, 128], false);
+fn usage_map(hash: &str) -> Grid<bool> {
+    let rows: Vec<Vec<bool>> = (0..128)
+        .map(|row| row_map(row as u8, hash).chars().map(|ch| ch == '1').collect())
+        .collect();
 
-    for row in 0..(128 as usize) {
-        let row_str = row_map(row as u8, hash);
-        for col in 0..(128 as usize) {
-            result[[row, col]] = row_str.chars().nth(col).unwrap() == '1';
-        }
-    }
+    Grid::from_rows(rows, false)
+}
 
-    result
+fn find_regions(map: &Grid<bool>) -> Vec<HashSet<(usize, usize)>> {
+    let used_cells: Vec<(usize, usize)> = map.positions().filter(|&(row, col)| map[(row, col)]).collect();
+    search::connected_components(used_cells, |&(row, col)| {
+        map.neighbours4(row, col).into_iter().filter(|&(r, c)| map[(r, c)]).collect::<Vec<(usize, usize)>>()
+    })
 }
 
-fn traverse_region(visited: &mut Array2D<bool>, map: &Array2D<bool>, row: usize, col: usize) {
-    visited[[row, col]] = true;
-    if col < 127 && map[[row, col + 1]] && !visited[[row, col + 1]] {
-        traverse_region(visited, map, row, col + 1);
-    }
-    if row < 127 && map[[row + 1, col]] && !visited[[row + 1, col]] {
-        traverse_region(visited, map, row + 1, col);
-    }
-    if col > 0 && map[[row, col - 1]] && !visited[[row, col - 1]] {
-        traverse_region(visited, map, row, col - 1);
-    }
-    if row > 0 && map[[row - 1, col]] && !visited[[row - 1, col]] {
-        traverse_region(visited, map, row - 1, col);
-    }
+fn count_regions(hash: &str) -> usize {
+    find_regions(&usage_map(hash)).len()
 }
 
-fn count_regions(hash: &str) -> i32 {
-    let map = usage_map(hash);
-    let mut visited: Array2D<bool> = Array2D::new([128, 128], false);
-    let mut region_count = 0;
+pub fn render() -> svg::Document {
+    const CELL: f64 = 6.0;
+    let map = usage_map(INPUT);
+    let regions = find_regions(&map);
+
+    let width = map.width() as f64 * CELL;
+    let height = map.height() as f64 * CELL;
+    let mut doc = svg::Document::new(width, height);
+    doc.rect(0.0, 0.0, width, height, &svg::Style::fill("white"));
 
-    for row in 0..128 {
-        for col in 0..128 {
-            if !visited[[row, col]] {
-                if map[[row, col]] {
-                    traverse_region(&mut visited, &map, row, col);
-                    region_count = region_count + 1;
-                }
-            }
+    for (idx, region) in regions.iter().enumerate() {
+        let style = svg::Style::fill(&svg::palette_color(idx));
+        for &(row, col) in region.iter() {
+            doc.rect(col as f64 * CELL, row as f64 * CELL, CELL, CELL, &style);
         }
     }
 
-    region_count
+    doc
 }
 
 fn usage_count(row: &String) -> usize {
@@ -62,7 +56,7 @@ fn usage_count(row: &String) -> usize {
 }
 
 pub fn run() {
-    let input = "hxtvlmkl";
+    let input = INPUT;
     println!("Day 14 result 1: {}", (0..128).map(|row| usage_count(&row_map(row, input))).sum::<usize>());
     println!("Day 14 result 2: {}", count_regions(input));
 }
@@ -1,4 +1,7 @@
 use common;
+use common::animation;
+use common::animation::Animation;
+use common::cycle;
 use std::collections::HashMap;
 
 #[derive(Debug)]
@@ -45,10 +48,16 @@ impl Move {
     }
 }
 
+#[derive(Clone)]
 struct State {
     line: Vec<Program>,
-    index: HashMap<char, usize>,
-    recorded :HashMap<String, (String, usize)>
+    index: HashMap<char, usize>
+}
+
+impl PartialEq for State {
+    fn eq(&self, other: &State) -> bool {
+        self.line == other.line
+    }
 }
 
 impl State {
@@ -56,7 +65,7 @@ impl State {
         let line: Vec<char> = (0..16).map(|n| (n + 'a' as u8) as char).collect();
         let index = HashMap::new();
 
-        let mut result = State { line, index, recorded: HashMap::new() };
+        let mut result = State { line, index };
         result.reindex();
         result
     }
@@ -97,23 +106,12 @@ impl State {
         }
     }
 
-    pub fn run(&mut self, n: usize, moves: &Vec<Move>) -> Option<usize> {
-        let from = self.to_string();
-
-        if self.recorded.contains_key(&from) {
-            let &(ref to, prev_idx) = self.recorded.get(&from).unwrap();
-            self.line = to.chars().collect();
-            Some(prev_idx)
-        } else {
-            self.reindex();
-            for mov in moves {
-                self.perfom(&mov);
-            }
-
-            let to = self.to_string();
-            self.recorded.insert(from, (to, n));
-            None
+    pub fn dance(&self, moves: &[Move]) -> State {
+        let mut next = self.clone();
+        for mov in moves {
+            next.perfom(mov);
         }
+        next
     }
 
     pub fn dump(&self) {
@@ -122,12 +120,37 @@ impl State {
         }
         println!();
     }
+}
+
+struct Dance<'a> {
+    state: State,
+    moves: &'a [Move],
+    count: usize,
+    until: usize
+}
+
+impl<'a> Animation for Dance<'a> {
+    fn frame(&self) -> String {
+        format!("{}  after {} dances", self.state.line.iter().collect::<String>(), self.count)
+    }
 
-    pub fn to_string(&self) -> String {
-        self.line.iter().collect()
+    fn advance(&mut self) -> bool {
+        self.state = self.state.dance(self.moves);
+        self.count += 1;
+        self.count < self.until
     }
 }
 
+// Plays the dances until the line of programs gets back to an already seen order
+pub fn animate(fps: f64) {
+    let input = common::read_data("day16.txt");
+    let moves: Vec<Move> = input.split(",").map(Move::parse).map(|r| r.unwrap()).collect();
+    let state = State::initial();
+    let (mu, lambda) = cycle::brent(|s| s.dance(&moves), &state);
+
+    animation::play(&mut Dance { state, moves: &moves, count: 0, until: mu + lambda }, fps);
+}
+
 pub fn run() {
     let input = common::read_data("day16.txt");
     let moves: Vec<Move> = input.split(",").map(Move::parse).map(|r| r.unwrap()).collect();
@@ -138,34 +161,17 @@ pub fn run() {
     print!("Initial state: ");
     state.dump();
 
-    state.run(0, &moves);
+    let after_one = state.dance(&moves);
 
     print!("State after one cycle: ");
-    state.dump();
+    after_one.dump();
+
+    let (mu, lambda) = cycle::brent(|s| s.dance(&moves), &state);
+    println!("Found cycle of length {} starting at {}", lambda, mu);
 
     const N: usize = 1000000000;
-    let mut n: usize = 1;
-    while n < (N - 1) {
-        let from = state.to_string();
-        let cycled = state.run(n, &moves);
-        let to = state.to_string();
-        println!("{}: {} => {} {:?}", n, from, to, cycled);
-        match cycled {
-            Some(prev_idx) => {
-                let d0 = (n - prev_idx) as usize;
-                let d = d0 * ((N - n) / d0);
-                if d > 0 {
-                    println!("Found cycle of length {} at {}, jumping to {}", d0, n, n + d);
-                    n = n + d;
-                } else {
-                    n = n + 1;
-                }
-            },
-            None =>
-                n = n + 1
-        }
-    }
+    state = cycle::nth_state(|s| s.dance(&moves), &state, N);
 
     print!("Final state: ");
     state.dump();
-}
\ No newline at end of file
+}
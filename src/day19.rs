@@ -1,7 +1,8 @@
-extern crate multiarray;
-
-use self::multiarray::*;
 use common;
+use common::animation;
+use common::animation::Animation;
+use common::grid::Grid;
+use common::svg;
 
 #[derive(Copy, Clone, Debug, Eq, Hash)]
 struct Coord {
@@ -44,13 +45,13 @@ impl PartialEq for Coord {
 }
 
 struct Map {
-    map: Array2D<char>,
+    map: Grid<char>,
     starting_point: Coord
 }
 
-fn find_starting_point(first_row: &Array1DRef<char>) -> Coord {
+fn find_starting_point(first_row: &[char]) -> Coord {
     let starting_point_x = first_row
-        .clone()
+        .iter()
         .position(|&ch| ch == '|')
         .unwrap();
 
@@ -62,17 +63,8 @@ fn find_starting_point(first_row: &Array1DRef<char>) -> Coord {
 
 impl Map {
     pub fn from_string(input: &str) -> Self {
-        let lines: Vec<&str> = input.split("\n").collect();
-        let max_width = lines.iter().map(|line| line.len()).max().unwrap();
-
-        let mut map = Array2D::new([lines.len(), max_width], ' ');
-        for (i, line) in lines.iter().enumerate() {
-            for (j, ch) in line.chars().enumerate() {
-                map[[i, j]] = ch;
-            }
-        }
-
-        let starting_point = find_starting_point(&map.eliminated_dim(0, 0));
+        let map = Grid::parse_chars(input);
+        let starting_point = find_starting_point(map.row(0));
 
         Map {
             map,
@@ -81,70 +73,154 @@ impl Map {
     }
 
     pub fn traverse(&self) -> (Vec<char>, usize) {
-        let mut result: Vec<char> = vec![];
-        let mut position = self.starting_point;
-        let mut direction = Direction::Down;
-        let mut ended = false;
-        let mut steps = 0;
-
-        while !ended && !self.is_outside(&position) {
-            position.mov(direction);
-
-            match self.at(&position) {
-                Some(next) => {
-                    if next.is_alphabetic() {
-                        if !result.contains(&next) {
-                            result.push(next);
-                        }
-                    } else if next == '+' {
-                        let left = self.at(&position.next(Direction::Left));
-                        let right = self.at(&position.next(Direction::Right));
-                        let up = self.at(&position.next(Direction::Up));
-                        let down = self.at(&position.next(Direction::Down));
-
-                        if (direction == Direction::Up || direction == Direction::Down) &&
-                            (left == Some('-') || left.map(|ch| ch.is_alphabetic()).unwrap_or(false)) {
-                            direction = Direction::Left;
-                        } else if (direction == Direction::Up || direction == Direction::Down) &&
-                            (right == Some('-') || right.map(|ch| ch.is_alphabetic()).unwrap_or(false)) {
-                            direction = Direction::Right;
-                        } else if (direction == Direction::Left || direction == Direction::Right) &&
-                            (up == Some('|') || up.map(|ch| ch.is_alphabetic()).unwrap_or(false)) {
-                            direction = Direction::Up;
-                        } else if (direction == Direction::Left || direction == Direction::Right) &&
-                            (down == Some('|') || down.map(|ch| ch.is_alphabetic()).unwrap_or(false)) {
-                            direction = Direction::Down;
-                        }
-                    }
-                    else if next == ' ' {
-                        ended = true;
-                    }
-
-                    steps = steps + 1;
-                },
-                None => {}
-            }
+        let mut traversal = Traversal::new(self);
+        while traversal.step() {
         }
 
-        (result, steps)
+        (traversal.letters, traversal.steps)
     }
 
     fn at(&self, coord: &Coord) -> Option<char> {
-        if self.is_outside(coord) {
-            None
-        } else {
-            Some(self.map[[coord.y as usize, coord.x as usize]])
-        }
+        self.map.get(coord.y as isize, coord.x as isize).cloned()
     }
 
     fn is_outside(&self, coord: &Coord) -> bool {
-        let height = self.map.extents()[0] as i32;
-        let width = self.map.extents()[1] as i32;
-        coord.x < 0 ||
-            coord.x >= width ||
-            coord.y < 0 ||
-            coord.y >= height
+        !self.map.contains(coord.y as isize, coord.x as isize)
+    }
+}
+
+struct Traversal<'a> {
+    map: &'a Map,
+    position: Coord,
+    direction: Direction,
+    ended: bool,
+    steps: usize,
+    letters: Vec<char>
+}
+
+impl<'a> Traversal<'a> {
+    pub fn new(map: &'a Map) -> Self {
+        Traversal {
+            map,
+            position: map.starting_point,
+            direction: Direction::Down,
+            ended: false,
+            steps: 0,
+            letters: vec![]
+        }
+    }
+
+    pub fn step(&mut self) -> bool {
+        if self.ended || self.map.is_outside(&self.position) {
+            return false;
+        }
+
+        let map = self.map;
+        let direction = self.direction;
+        self.position.mov(direction);
+        let position = self.position;
+
+        if let Some(next) = map.at(&position) {
+            if next.is_alphabetic() {
+                if !self.letters.contains(&next) {
+                    self.letters.push(next);
+                }
+            } else if next == '+' {
+                let left = map.at(&position.next(Direction::Left));
+                let right = map.at(&position.next(Direction::Right));
+                let up = map.at(&position.next(Direction::Up));
+                let down = map.at(&position.next(Direction::Down));
+
+                if (direction == Direction::Up || direction == Direction::Down) &&
+                    (left == Some('-') || left.map(|ch| ch.is_alphabetic()).unwrap_or(false)) {
+                    self.direction = Direction::Left;
+                } else if (direction == Direction::Up || direction == Direction::Down) &&
+                    (right == Some('-') || right.map(|ch| ch.is_alphabetic()).unwrap_or(false)) {
+                    self.direction = Direction::Right;
+                } else if (direction == Direction::Left || direction == Direction::Right) &&
+                    (up == Some('|') || up.map(|ch| ch.is_alphabetic()).unwrap_or(false)) {
+                    self.direction = Direction::Up;
+                } else if (direction == Direction::Left || direction == Direction::Right) &&
+                    (down == Some('|') || down.map(|ch| ch.is_alphabetic()).unwrap_or(false)) {
+                    self.direction = Direction::Down;
+                }
+            }
+            else if next == ' ' {
+                self.ended = true;
+            }
+
+            self.steps += 1;
+        }
+
+        true
     }
+
+    // A window of the map centered on the current position
+    fn view(&self, half_width: i32, half_height: i32) -> String {
+        let mut lines: Vec<String> = Vec::new();
+        for y in (self.position.y - half_height)..(self.position.y + half_height + 1) {
+            let line: String = ((self.position.x - half_width)..(self.position.x + half_width + 1))
+                .map(|x| {
+                    let coord = Coord { x, y };
+                    if coord == self.position { '@' } else { self.map.at(&coord).unwrap_or(' ') }
+                })
+                .collect();
+            lines.push(line);
+        }
+        lines.push(format!("steps: {} letters: {}", self.steps, self.letters.iter().collect::<String>()));
+
+        lines.join("\n")
+    }
+}
+
+impl<'a> Animation for Traversal<'a> {
+    fn frame(&self) -> String {
+        self.view(30, 10)
+    }
+
+    fn advance(&mut self) -> bool {
+        self.step()
+    }
+}
+
+pub fn animate(fps: f64) {
+    let input = common::read_data("day19.txt");
+    let map = Map::from_string(input.as_str());
+    animation::play(&mut Traversal::new(&map), fps);
+}
+
+pub fn render() -> svg::Document {
+    const CELL: f64 = 8.0;
+    let input = common::read_data("day19.txt");
+    let map = Map::from_string(input.as_str());
+
+    let width = map.map.width() as f64 * CELL;
+    let height = map.map.height() as f64 * CELL;
+    let mut doc = svg::Document::new(width, height);
+    doc.rect(0.0, 0.0, width, height, &svg::Style::fill("white"));
+
+    let tube = svg::Style::fill("silver");
+    let corner = svg::Style::fill("gray");
+    let letter = svg::Style::fill("crimson");
+    let mut letters = Vec::new();
+    for (row, col) in map.map.positions() {
+        let ch = map.map[(row, col)];
+        let (x, y) = (col as f64 * CELL, row as f64 * CELL);
+        match ch {
+            ' ' => {},
+            '+' => doc.rect(x, y, CELL, CELL, &corner),
+            '|' | '-' => doc.rect(x, y, CELL, CELL, &tube),
+            _ => letters.push((x, y, ch))
+        }
+    }
+
+    // The highlights overlap the neighbouring cells, so they go on top of every tube
+    for (x, y, ch) in letters {
+        doc.rect(x - CELL, y - CELL, CELL * 3.0, CELL * 3.0, &letter);
+        doc.text(x + CELL / 2.0, y + CELL / 2.0, CELL * 2.0, &ch.to_string(), "white");
+    }
+
+    doc
 }
 
 pub fn run() {
@@ -1,31 +1,140 @@
-extern crate multiarray;
-
-use self::multiarray::*;
+use common;
+use common::grid::Grid;
 use std::cmp;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::prelude::*;
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidValue(usize, usize, String),
+    UnterminatedQuote(usize, usize),
+    UnknownDelimiter(String)
+}
 
-fn read_matrix(input: &str) -> Array2D<u16> {
-    let rows: Vec<&str> = input.split('\n').collect();
-    let row_count = rows.len();
-    let first_cols: Vec<&str> = rows[0].split_whitespace().collect();
-    let col_count = first_cols.len();
-    let mut result = Array2D::new([row_count, col_count], 0);
-
-    for row_idx in 0..row_count {
-        let row = rows[row_idx];
-        let cols: Vec<u16> = row.split_whitespace().map(|s| s.parse::<u16>().unwrap()).collect();
-        for col_idx in 0..col_count {
-            result[[row_idx, col_idx]] = cols[col_idx];
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidValue(row, col, ref value) => write!(f, "invalid value {:?} in row {}, column {}", value, row, col),
+            Error::UnterminatedQuote(row, col) => write!(f, "unterminated quote in row {}, column {}", row, col),
+            Error::UnknownDelimiter(ref name) => write!(f, "unknown delimiter {}", name)
         }
     }
+}
 
-    result
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Delimiter {
+    Auto,
+    Comma,
+    Tab,
+    Whitespace
+}
+
+impl Delimiter {
+    pub fn parse(s: &str) -> Result<Delimiter, Error> {
+        match s {
+            "auto" => Ok(Delimiter::Auto),
+            "comma" | "," => Ok(Delimiter::Comma),
+            "tab" => Ok(Delimiter::Tab),
+            "space" | "whitespace" => Ok(Delimiter::Whitespace),
+            _ => Err(Error::UnknownDelimiter(String::from(s)))
+        }
+    }
+
+    fn detect(line: &str) -> Delimiter {
+        if line.contains(',') {
+            Delimiter::Comma
+        } else if line.contains('\t') {
+            Delimiter::Tab
+        } else {
+            Delimiter::Whitespace
+        }
+    }
+}
+
+// Splits a line into (column, field) pairs. Fields may be enclosed in double quotes,
+// with "" standing for a literal quote inside them. Columns are 1-based.
+fn split_fields(line: &str, row: usize, delimiter: Delimiter) -> Result<Vec<(usize, String)>, Error> {
+    if delimiter == Delimiter::Whitespace {
+        return Ok(line.split_whitespace().enumerate().map(|(idx, field)| (idx + 1, String::from(field))).collect());
+    }
+
+    let separator = if delimiter == Delimiter::Comma { ',' } else { '\t' };
+    let mut fields: Vec<(usize, String)> = Vec::new();
+    let mut chars = line.chars().peekable();
+    let mut column = 1;
+
+    loop {
+        let mut field = String::new();
+        while chars.peek() == Some(&' ') {
+            chars.next();
+        }
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    },
+                    Some('"') => break,
+                    Some(ch) => field.push(ch),
+                    None => return Err(Error::UnterminatedQuote(row, column))
+                }
+            }
+        }
+        while let Some(&ch) = chars.peek() {
+            if ch == separator {
+                break;
+            }
+            field.push(ch);
+            chars.next();
+        }
+        fields.push((column, field));
+
+        match chars.next() {
+            Some(_) => column += 1,
+            None => break
+        }
+    }
+
+    Ok(fields)
+}
+
+// Reads a spreadsheet with possibly different row lengths, shorter rows are padded with None. Blank lines
+// are skipped, and the first `header_rows` non-blank lines are ignored. Rows and columns in errors are 1-based.
+pub fn read_matrix(input: &str, delimiter: Delimiter, header_rows: usize) -> Result<Grid<Option<u64>>, Error> {
+    let lines: Vec<(usize, &str)> = input.lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line))
+        .filter(|&(_, line)| !line.trim().is_empty())
+        .skip(header_rows)
+        .collect();
+
+    let delimiter = match (delimiter, lines.first()) {
+        (Delimiter::Auto, Some(&(_, line))) => Delimiter::detect(line),
+        (delimiter, _) => delimiter
+    };
+
+    let mut rows: Vec<Vec<Option<u64>>> = Vec::new();
+    for (row, line) in lines {
+        let mut values: Vec<Option<u64>> = Vec::new();
+        for (col, field) in split_fields(line, row, delimiter)? {
+            let value = field.trim().parse::<u64>().map_err(|_| Error::InvalidValue(row, col, field.clone()))?;
+            values.push(Some(value));
+        }
+        rows.push(values);
+    }
+
+    Ok(Grid::from_rows(rows, None))
 }
 
-fn min_max_in(lane: Array1DRef<u16>) -> (u16, u16) {
-    assert!(lane.extents()[0] > 0);
+fn min_max_in(lane: &[u64]) -> (u64, u64) {
+    assert!(!lane.is_empty());
 
-    let mut min: Option<u16> = None;
-    let mut max: Option<u16> = None;
+    let mut min: Option<u64> = None;
+    let mut max: Option<u64> = None;
     for item in lane {
         min = Some(cmp::min(*item, min.unwrap_or(*item)));
         max = Some(cmp::max(*item, max.unwrap_or(*item)));
@@ -34,27 +143,191 @@ fn min_max_in(lane: Array1DRef<u16>) -> (u16, u16) {
     (min.unwrap(), max.unwrap())
 }
 
-fn sum_of_divided(lane: Array1DRef<u16>) -> u32 {
-    let mut sum: u32 = 0;
-    let len = lane.extents()[0];
+// Column indices of the two values, the dividend being a multiple of the divisor
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DivisiblePair {
+    pub dividend: usize,
+    pub divisor: usize,
+    pub quotient: u64
+}
 
-    for i in 0..len {
-        for j in 0..len {
-            if i != j {
-                let a = lane[i];
-                let b = lane[j];
+// The pairs of columns whose values divide evenly, sorted. A value is never divided by an equal one,
+// so duplicates do not count, and zero is divisible by every non-zero value with a quotient of 0.
+// For every distinct divisor b, either enumerates its multiples up to the row maximum or scans the
+// larger distinct values, whichever is fewer, so dense wide rows avoid comparing every pair
+pub fn divisible_pairs(row: &[u64]) -> Vec<DivisiblePair> {
+    let mut indices: BTreeMap<u64, Vec<usize>> = BTreeMap::new();
+    for (idx, &value) in row.iter().enumerate() {
+        indices.entry(value).or_default().push(idx);
+    }
 
-                if (a % b) == 0 {
-                    sum += (a / b) as u32;
+    let mut result: Vec<DivisiblePair> = Vec::new();
+    let zeros = indices.remove(&0).unwrap_or_default();
+    for &zero in zeros.iter() {
+        for (idx, &value) in row.iter().enumerate() {
+            if value != 0 {
+                result.push(DivisiblePair { dividend: zero, divisor: idx, quotient: 0 });
+            }
+        }
+    }
+
+    let values: Vec<u64> = indices.keys().cloned().collect();
+    let max = values.last().cloned().unwrap_or(0);
+    for (pos, &b) in values.iter().enumerate() {
+        let larger = &values[pos + 1..];
+        let multiples: Vec<u64> =
+            if max / b < larger.len() as u64 {
+                (2..(max / b + 1)).map(|m| b * m).filter(|a| indices.contains_key(a)).collect()
+            } else {
+                larger.iter().cloned().filter(|a| a.is_multiple_of(b)).collect()
+            };
+
+        for a in multiples {
+            for &dividend in indices[&a].iter() {
+                for &divisor in indices[&b].iter() {
+                    result.push(DivisiblePair { dividend, divisor, quotient: a / b });
                 }
             }
         }
     }
+    result.sort();
 
-    sum
+    result
 }
 
-pub fn run() {
+// Each unordered pair of columns is reported at most once, with the same rules as `divisible_pairs`
+fn pair_of(row: &[u64], i: usize, j: usize) -> Option<DivisiblePair> {
+    let (small, big) = if row[i] < row[j] { (i, j) } else { (j, i) };
+    let (a, b) = (row[big], row[small]);
+
+    if a == b {
+        None
+    } else if b == 0 {
+        Some(DivisiblePair { dividend: small, divisor: big, quotient: 0 })
+    } else if a.is_multiple_of(b) {
+        Some(DivisiblePair { dividend: big, divisor: small, quotient: a / b })
+    } else {
+        None
+    }
+}
+
+// Compares every pair, kept to cross-check `divisible_pairs`
+#[allow(dead_code)]
+pub fn divisible_pairs_naive(row: &[u64]) -> Vec<DivisiblePair> {
+    let mut result: Vec<DivisiblePair> = Vec::new();
+    for i in 0..row.len() {
+        for j in (i + 1)..row.len() {
+            if let Some(pair) = pair_of(row, i, j) {
+                result.push(pair);
+            }
+        }
+    }
+    result.sort();
+
+    result
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+// Checksums get only non-empty rows. They are u128, so sums of u64 values can not overflow.
+pub trait RowChecksum {
+    fn name(&self) -> &'static str;
+    fn checksum(&self, row: &[u64]) -> u128;
+}
+
+struct Range;
+struct DivisibleQuotient;
+struct Sum;
+struct MedianSpread;
+struct MaxGcdPair;
+
+impl RowChecksum for Range {
+    fn name(&self) -> &'static str { "range" }
+
+    fn checksum(&self, row: &[u64]) -> u128 {
+        let (min, max) = min_max_in(row);
+        (max - min) as u128
+    }
+}
+
+impl RowChecksum for DivisibleQuotient {
+    fn name(&self) -> &'static str { "divisible" }
+
+    fn checksum(&self, row: &[u64]) -> u128 {
+        divisible_pairs(row).iter().map(|pair| pair.quotient as u128).sum()
+    }
+}
+
+impl RowChecksum for Sum {
+    fn name(&self) -> &'static str { "sum" }
+
+    fn checksum(&self, row: &[u64]) -> u128 {
+        row.iter().map(|&value| value as u128).sum()
+    }
+}
+
+// Largest distance of any value from the (lower) median of the row
+impl RowChecksum for MedianSpread {
+    fn name(&self) -> &'static str { "median-spread" }
+
+    fn checksum(&self, row: &[u64]) -> u128 {
+        let mut sorted = row.to_vec();
+        sorted.sort();
+        let median = sorted[(sorted.len() - 1) / 2];
+        cmp::max(median - sorted[0], sorted[sorted.len() - 1] - median) as u128
+    }
+}
+
+impl RowChecksum for MaxGcdPair {
+    fn name(&self) -> &'static str { "max-gcd" }
+
+    fn checksum(&self, row: &[u64]) -> u128 {
+        let mut result = 0;
+        for i in 0..row.len() {
+            for j in (i + 1)..row.len() {
+                result = cmp::max(result, gcd(row[i], row[j]));
+            }
+        }
+        result as u128
+    }
+}
+
+pub fn strategy(name: &str) -> Option<Box<dyn RowChecksum>> {
+    match name {
+        "range" => Some(Box::new(Range)),
+        "divisible" => Some(Box::new(DivisibleQuotient)),
+        "sum" => Some(Box::new(Sum)),
+        "median-spread" => Some(Box::new(MedianSpread)),
+        "max-gcd" => Some(Box::new(MaxGcdPair)),
+        _ => None
+    }
+}
+
+// A row checksum is at most its length times u64::MAX, so the total needs more rows than fit in memory to overflow
+fn checksum(matrix: &Grid<Option<u64>>, strategy: &dyn RowChecksum) -> u128 {
+    matrix.rows().iter()
+        .map(|row| row.iter().filter_map(|&value| value).collect::<Vec<u64>>())
+        .filter(|row| !row.is_empty())
+        .map(|row| strategy.checksum(&row))
+        .sum()
+}
+
+fn example() {
+    let csv = "a,b,c\n5,\"9\",2,8\n9,4\n\"3\",8,6,100000000000\n";
+    let matrix = read_matrix(csv, Delimiter::Auto, 1).unwrap();
+    println!("Day2 example result range: {}", checksum(&matrix, &Range));
+
+    match read_matrix("5 1 9\n7 x 3", Delimiter::Whitespace, 0) {
+        Ok(_) => {},
+        Err(err) => println!("Day2 example error: {}", err)
+    }
+}
+
+// Options: --checksum with a comma separated list of row checksum names (the two puzzle parts by default),
+// --input with a spreadsheet file, --delimiter auto|comma|tab|space and --header with the number of header rows
+pub fn run(options: &[String]) {
     let input = r#"493	458	321	120	49	432	433	92	54	452	41	461	388	409	263	58
 961	98	518	188	958	114	1044	881	948	590	972	398	115	116	451	492
 76	783	709	489	617	72	824	452	748	737	691	90	94	77	84	756
@@ -72,17 +345,105 @@ pub fn run() {
 1165	1119	194	280	223	1181	267	898	1108	124	618	1135	817	997	129	227
 404	1757	358	2293	2626	87	613	95	1658	147	75	930	2394	2349	86	385"#;
 
-    let input_matrix = read_matrix(input);
-    let n = input_matrix.extents()[0];
+    let contents = common::option_value(options, "--input").map(|path| {
+        let mut contents = String::new();
+        File::open(path).and_then(|mut file| file.read_to_string(&mut contents)).expect("Unable to read the file");
+        contents
+    });
+    let delimiter = match Delimiter::parse(common::option_value(options, "--delimiter").unwrap_or("auto")) {
+        Ok(delimiter) => delimiter,
+        Err(err) => {
+            eprintln!("{}, expected auto, comma, tab or space", err);
+            return;
+        }
+    };
+    let header_rows = common::option_value(options, "--header").map(|s| s.parse::<usize>().expect("Invalid header row count")).unwrap_or(0);
 
-    let mut sum1: u32 = 0;
-    let mut sum2: u32 = 0;
-    for row_idx in 0..n {
-        let row = input_matrix.eliminated_dim(0, row_idx);
-        let (min, max) = min_max_in(row);
-        sum1 += (max - min) as u32;
-        sum2 += sum_of_divided(row);
+    if contents.is_none() {
+        example();
+    }
+
+    match read_matrix(contents.as_ref().map_or(input, |s| s.as_str()), delimiter, header_rows) {
+        Ok(input_matrix) => {
+            for name in common::option_value(options, "--checksum").unwrap_or("range,divisible").split(',') {
+                match strategy(name) {
+                    Some(strategy) => println!("Day2 result {}: {}", strategy.name(), checksum(&input_matrix, strategy.as_ref())),
+                    None => eprintln!("Unknown row checksum: {}", name)
+                }
+            }
+        },
+        Err(err) => eprintln!("Could not read the spreadsheet: {}", err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::testing::Lcg;
+
+    // Pseudo-random wide rows, with duplicates and zeros
+    #[test]
+    fn divisible_pairs_match_naive() {
+        let mut rng = Lcg::new(12345);
+        for &(width, range) in [(8, 10), (200, 50), (3000, 5000), (3000, 1000000)].iter() {
+            let row: Vec<u64> = (0..width).map(|_| rng.below(range)).collect();
+            assert_eq!(divisible_pairs(&row), divisible_pairs_naive(&row), "{} values below {}", width, range);
+        }
     }
-    println!("Day2 result 1: {}", sum1);
-    println!("Day2 result 2: {}", sum2);
-}
\ No newline at end of file
+
+    #[test]
+    fn puzzle_examples() {
+        let range = read_matrix("5 1 9 5\n7 5 3\n2 4 6 8", Delimiter::Whitespace, 0).unwrap();
+        assert_eq!(checksum(&range, &Range), 18);
+        let divisible = read_matrix("5 9 2 8\n9 4 7 3\n3 8 6 5", Delimiter::Whitespace, 0).unwrap();
+        assert_eq!(checksum(&divisible, &DivisibleQuotient), 9);
+    }
+
+    #[test]
+    fn duplicates_are_not_divided() {
+        assert_eq!(divisible_pairs(&[4, 4, 2]), vec![
+            DivisiblePair { dividend: 0, divisor: 2, quotient: 2 },
+            DivisiblePair { dividend: 1, divisor: 2, quotient: 2 }
+        ]);
+        assert_eq!(divisible_pairs(&[7, 7, 0, 0]), vec![
+            DivisiblePair { dividend: 2, divisor: 0, quotient: 0 },
+            DivisiblePair { dividend: 2, divisor: 1, quotient: 0 },
+            DivisiblePair { dividend: 3, divisor: 0, quotient: 0 },
+            DivisiblePair { dividend: 3, divisor: 1, quotient: 0 }
+        ]);
+    }
+
+    #[test]
+    fn ragged_rows_are_padded() {
+        let matrix = read_matrix("a,b\n5,\"9\",2\n\n9,4\n", Delimiter::Auto, 1).unwrap();
+        assert_eq!((matrix.height(), matrix.width()), (2, 3));
+        assert_eq!(matrix.row(1), &[Some(9), Some(4), None]);
+        assert_eq!(checksum(&matrix, &Range), 7 + 5);
+    }
+
+    #[test]
+    fn delimiters() {
+        assert_eq!(Delimiter::parse(",").unwrap(), Delimiter::Comma);
+        assert_eq!(Delimiter::parse("space").unwrap(), Delimiter::Whitespace);
+        match Delimiter::parse("semicolon") {
+            Err(Error::UnknownDelimiter(ref name)) => assert_eq!(name, "semicolon"),
+            other => panic!("unexpected {:?}", other)
+        }
+        let matrix = read_matrix("1\t2\n3\t\"4\"", Delimiter::Auto, 0).unwrap();
+        assert_eq!(matrix.row(1), &[Some(3), Some(4)]);
+    }
+
+    #[test]
+    fn checksums_do_not_overflow() {
+        let max = u64::MAX as u128;
+        let matrix = read_matrix("18446744073709551615\n1", Delimiter::Whitespace, 0).unwrap();
+        assert_eq!(checksum(&matrix, &Sum), max + 1);
+
+        let matrix = read_matrix("0 18446744073709551615\n0 18446744073709551615", Delimiter::Whitespace, 0).unwrap();
+        assert_eq!(checksum(&matrix, &Range), 2 * max);
+        assert_eq!(checksum(&matrix, &Sum), 2 * max);
+
+        let matrix = read_matrix("1 18446744073709551615 18446744073709551615", Delimiter::Whitespace, 0).unwrap();
+        assert_eq!(checksum(&matrix, &DivisibleQuotient), 2 * max);
+    }
+}
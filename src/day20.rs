@@ -1,6 +1,9 @@
 extern crate regex;
 
 use common;
+use common::animation;
+use common::animation::Animation;
+use common::svg;
 use self::regex::Regex;
 use std::cmp::Ordering;
 use std::collections::HashMap;
@@ -139,6 +142,41 @@ impl ParticleSystem {
     }
 }
 
+impl Animation for ParticleSystem {
+    // Projects the x and y coordinates onto a character grid fitted around all the particles
+    fn frame(&self) -> String {
+        const WIDTH: usize = 79;
+        const HEIGHT: usize = 39;
+        let min_x = self.particles.iter().map(|p| p.position.x).min().unwrap_or(0).min(0);
+        let max_x = self.particles.iter().map(|p| p.position.x).max().unwrap_or(0).max(0);
+        let min_y = self.particles.iter().map(|p| p.position.y).min().unwrap_or(0).min(0);
+        let max_y = self.particles.iter().map(|p| p.position.y).max().unwrap_or(0).max(0);
+        let scale = |v: i64, min: i64, max: i64, size: usize| ((v - min) as f64 / ((max - min).max(1) as f64) * (size - 1) as f64).round() as usize;
+
+        let mut canvas = vec![vec![' '; WIDTH]; HEIGHT];
+        canvas[scale(0, min_y, max_y, HEIGHT)][scale(0, min_x, max_x, WIDTH)] = '+';
+        for particle in self.particles.iter() {
+            let row = scale(particle.position.y, min_y, max_y, HEIGHT);
+            let col = scale(particle.position.x, min_x, max_x, WIDTH);
+            canvas[row][col] = '*';
+        }
+
+        let lines: Vec<String> = canvas.iter().map(|row| row.iter().collect()).collect();
+        format!("{}\nx: {}..{} y: {}..{}", lines.join("\n"), min_x, max_x, min_y, max_y)
+    }
+
+    fn advance(&mut self) -> bool {
+        self.step_all() > 0
+    }
+}
+
+// Plays the simulation until no particle gets closer to the origin anymore
+pub fn animate(fps: f64) {
+    let input = common::read_data("day20.txt");
+    let particles: Vec<Particle> = input.split("\n").enumerate().map(|(idx, line)| Particle::parse(idx, line)).collect();
+    animation::play(&mut ParticleSystem::new(particles), fps);
+}
+
 fn compare_particle(a: &Particle, b: &Particle) -> Ordering {
     let origin = Vector { x: 0, y: 0, z: 0 };
     a.position.manhattan_distance(&origin).cmp(&b.position.manhattan_distance(&origin))
@@ -161,6 +199,34 @@ fn part2(particles: Vec<Particle>) {
     println!("Day 20 result 2: {}", particle_system.particles.len());
 }
 
+pub fn render(tick: usize) -> svg::Document {
+    let input = common::read_data("day20.txt");
+    let particles: Vec<Particle> = input.split("\n").enumerate().map(|(idx, line)| Particle::parse(idx, line)).collect();
+    let mut particle_system = ParticleSystem::new(particles);
+    particle_system.run_more(tick);
+
+    let xs: Vec<f64> = particle_system.particles.iter().map(|p| p.position.x as f64).collect();
+    let ys: Vec<f64> = particle_system.particles.iter().map(|p| p.position.y as f64).collect();
+    let min_x = xs.iter().cloned().fold(0.0, f64::min);
+    let max_x = xs.iter().cloned().fold(0.0, f64::max);
+    let min_y = ys.iter().cloned().fold(0.0, f64::min);
+    let max_y = ys.iter().cloned().fold(0.0, f64::max);
+    let extent = (max_x - min_x).max(max_y - min_y).max(1.0);
+    let margin = extent * 0.05;
+    let radius = extent * 0.004;
+
+    let mut doc = svg::Document::new(800.0, 800.0)
+        .with_view_box(min_x - margin, min_y - margin, extent + 2.0 * margin, extent + 2.0 * margin);
+    doc.rect(min_x - margin, min_y - margin, extent + 2.0 * margin, extent + 2.0 * margin, &svg::Style::fill("white"));
+    doc.circle(0.0, 0.0, radius * 2.0, &svg::Style::stroke("black", radius / 2.0));
+
+    for (x, y) in xs.into_iter().zip(ys) {
+        doc.circle(x, y, radius, &svg::Style::fill("steelblue"));
+    }
+
+    doc
+}
+
 pub fn run() {
     let input = common::read_data("day20.txt");
     let particles: Vec<Particle> = input.split("\n").enumerate().map(|(idx, line)| Particle::parse(idx, line)).collect();
@@ -1,10 +1,12 @@
 
-use std::collections::HashMap;
+use common::svg;
+use std::cmp;
+use std::convert::TryFrom;
 
 #[derive(Copy, Clone, Debug, Eq, Hash)]
 struct Coord {
-    x: i32,
-    y: i32,
+    x: i64,
+    y: i64,
 }
 
 impl PartialEq for Coord {
@@ -14,8 +16,8 @@ impl PartialEq for Coord {
 }
 
 impl Coord {
-    pub fn manhattan_distance(&self, other: &Coord) -> u32 {
-        ((self.x - other.x).abs() + (self.y - other.y).abs()) as u32
+    pub fn manhattan_distance(&self, other: &Coord) -> u64 {
+        (self.x - other.x).unsigned_abs() + (self.y - other.y).unsigned_abs()
     }
 
     pub fn left(&self) -> Coord {
@@ -37,23 +39,306 @@ impl Coord {
 
 
 
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Direction {
+    Right,
+    Up,
+    Left,
+    Down
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Rotation {
+    CounterClockwise,
+    Clockwise
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum YAxis {
+    Down,
+    Up
+}
+
+// The spiral is laid out by the direction of its first step and its rotation sense, both as seen
+// on screen. The y axis orientation only changes how the resulting positions are reported.
+#[derive(Copy, Clone, Debug)]
 struct CoordinateSystem {
-    cache: HashMap<u32, Coord>
+    start: Direction,
+    rotation: Rotation,
+    y_axis: YAxis
 }
 
 impl CoordinateSystem {
     pub fn new() -> Self {
-        CoordinateSystem {
-            cache: HashMap::new()
+        CoordinateSystem::with_layout(Direction::Right, Rotation::CounterClockwise, YAxis::Down)
+    }
+
+    pub fn with_layout(start: Direction, rotation: Rotation, y_axis: YAxis) -> Self {
+        CoordinateSystem { start, rotation, y_axis }
+    }
+
+    pub fn coords_of(&self, n: u64) -> Coord {
+        self.apply_layout(CoordinateSystem::canonical_coords_of(n))
+    }
+
+    pub fn index_of(&self, coord: &Coord) -> Option<u64> {
+        self.undo_layout(coord).and_then(|coord| CoordinateSystem::canonical_index_of(&coord))
+    }
+
+    // Canonical coordinates have y pointing down, and the spiral starts right, turning counter-clockwise
+    fn apply_layout(&self, coord: Coord) -> Coord {
+        let (x, y) = (coord.x, coord.y);
+        let y = if self.rotation == Rotation::Clockwise { -y } else { y };
+        let (x, y) = match self.start {
+            Direction::Right => (x, y),
+            Direction::Down => (-y, x),
+            Direction::Left => (-x, -y),
+            Direction::Up => (y, -x)
+        };
+        let y = if self.y_axis == YAxis::Up { -y } else { y };
+
+        Coord { x, y }
+    }
+
+    // None if a coordinate can not be negated (i64::MIN), such a square is far outside the u64 range anyway
+    fn undo_layout(&self, coord: &Coord) -> Option<Coord> {
+        let (x, y) = (coord.x, coord.y);
+        let y = if self.y_axis == YAxis::Up { y.checked_neg()? } else { y };
+        let (x, y) = match self.start {
+            Direction::Right => (x, y),
+            Direction::Down => (y, x.checked_neg()?),
+            Direction::Left => (x.checked_neg()?, y.checked_neg()?),
+            Direction::Up => (y.checked_neg()?, x)
+        };
+        let y = if self.rotation == Rotation::Clockwise { y.checked_neg()? } else { y };
+
+        Some(Coord { x, y })
+    }
+
+    // Ring `level` holds the squares (2 * level - 1)^2 + 1 ..= (2 * level + 1)^2,
+    // starting right below its bottom right corner and going counter-clockwise
+    fn canonical_coords_of(n: u64) -> Coord {
+        assert_ne!(n, 0);
+        if n == 1 {
+            return Coord { x: 0, y: 0 };
+        }
+
+        let level = (n - 1).isqrt().div_ceil(2);
+        let first = (2 * level - 1) * (2 * level - 1) + 1;
+        let side = 2 * level;
+        let idx = n - first;
+        let segment = idx / side;
+        let pos = (idx % side) as i64;
+        let l = level as i64;
+
+        match segment {
+            0 => Coord { x: l, y: l - 1 - pos },
+            1 => Coord { x: l - 1 - pos, y: -l },
+            2 => Coord { x: -l, y: -l + 1 + pos },
+            3 => Coord { x: -l + 1 + pos, y: l },
+            _ => panic!("Segment was {}", segment)
         }
     }
 
-    pub fn coords_of(&mut self, n: u32) -> Coord {
-        let result: &Coord = self.cache.entry(n.to_owned()).or_insert_with(|| { return CoordinateSystem::calc_coords_of(n) });
-        *result
+    // Inverse of `canonical_coords_of`, None if the index would not fit in an u64. Ring 2^31 is
+    // the last one starting in the u64 range, so the arithmetic below can not overflow an u128.
+    fn canonical_index_of(coord: &Coord) -> Option<u64> {
+        let l = cmp::max(coord.x.unsigned_abs(), coord.y.unsigned_abs()) as u128;
+        if l == 0 {
+            return Some(1);
+        }
+        if l > 1 << 31 {
+            return None;
+        }
+
+        let (x, y) = (coord.x as i128, coord.y as i128);
+        let li = l as i128;
+        let first = (2 * l - 1) * (2 * l - 1) + 1;
+        let side = 2 * l;
+        let (segment, pos) =
+            if x == li && y < li {
+                (0, li - 1 - y)
+            } else if y == -li {
+                (1, li - 1 - x)
+            } else if x == -li {
+                (2, y + li - 1)
+            } else {
+                (3, x + li - 1)
+            };
+
+        u64::try_from(first + segment * side + pos as u128).ok()
     }
+}
 
-    fn calc_coords_of(n: u32) -> Coord {
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Neighbourhood {
+    Four,
+    Eight
+}
+
+impl Neighbourhood {
+    fn of(&self, coord: &Coord) -> Vec<Coord> {
+        let mut result = vec![coord.left(), coord.right(), coord.up(), coord.down()];
+        if *self == Neighbourhood::Eight {
+            result.extend(vec![coord.left().up(), coord.right().up(), coord.left().down(), coord.right().down()]);
+        }
+        result
+    }
+}
+
+// Combines the values of the already filled neighbours, None stops the sequence (on overflow for example)
+struct Fold {
+    initial: u128,
+    combine: Box<dyn Fn(u128, u128) -> Option<u128>>
+}
+
+impl Fold {
+    pub fn new<F: Fn(u128, u128) -> Option<u128> + 'static>(initial: u128, combine: F) -> Self {
+        Fold { initial, combine: Box::new(combine) }
+    }
+
+    pub fn sum() -> Self {
+        Fold::new(0, |acc, value| acc.checked_add(value))
+    }
+
+    pub fn product_mod(p: u128) -> Self {
+        Fold::new(1, move |acc, value| acc.checked_mul(value).map(|product| product % p))
+    }
+}
+
+// The stress test sequence yielding (index, coord, value). Values are stored densely by their
+// spiral index, and neighbours are looked up through `index_of`.
+struct StressTest<'a> {
+    csys: &'a CoordinateSystem,
+    neighbourhood: Neighbourhood,
+    fold: Fold,
+    values: Vec<u128>,
+    seed: u128,
+    finished: bool
+}
+
+impl<'a> StressTest<'a> {
+    pub fn new(csys: &'a CoordinateSystem, neighbourhood: Neighbourhood, fold: Fold) -> Self {
+        StressTest { csys, neighbourhood, fold, values: Vec::new(), seed: 1, finished: false }
+    }
+
+    pub fn with_seed(self, seed: u128) -> Self {
+        StressTest { seed, ..self }
+    }
+
+    fn next_value(&self, n: u64, coord: &Coord) -> Option<u128> {
+        if n == 1 {
+            return Some(self.seed);
+        }
+
+        let mut acc = self.fold.initial;
+        for neighbour in self.neighbourhood.of(coord) {
+            if let Some(idx) = self.csys.index_of(&neighbour) {
+                if idx < n {
+                    acc = (self.fold.combine)(acc, self.values[(idx - 1) as usize])?;
+                }
+            }
+        }
+        Some(acc)
+    }
+}
+
+impl<'a> Iterator for StressTest<'a> {
+    type Item = (u64, Coord, u128);
+
+    fn next(&mut self) -> Option<(u64, Coord, u128)> {
+        if self.finished {
+            return None;
+        }
+
+        let n = self.values.len() as u64 + 1;
+        let coord = self.csys.coords_of(n);
+        match self.next_value(n, &coord) {
+            Some(value) => {
+                self.values.push(value);
+                Some((n, coord, value))
+            },
+            None => {
+                self.finished = true;
+                None
+            }
+        }
+    }
+}
+
+fn find_first_larger_cell(csys: &CoordinateSystem, than: u128) -> u128 {
+    let (_, _, value) = StressTest::new(csys, Neighbourhood::Eight, Fold::sum())
+        .find(|&(_, _, value)| value >= than)
+        .unwrap();
+
+    value
+}
+
+pub fn render() -> svg::Document {
+    const LEVELS: i64 = 4;
+    const CELL: f64 = 60.0;
+    let side = (2 * LEVELS + 1) as u64;
+
+    let csys = CoordinateSystem::new();
+    let cells: Vec<(Coord, u128)> = StressTest::new(&csys, Neighbourhood::Eight, Fold::sum())
+        .take((side * side) as usize)
+        .map(|(_, coord, value)| (coord, value))
+        .collect();
+    let size = CELL * side as f64;
+    let center = |coord: &Coord| ((coord.x + LEVELS) as f64 * CELL + CELL / 2.0, (coord.y + LEVELS) as f64 * CELL + CELL / 2.0);
+
+    let mut doc = svg::Document::new(size, size);
+    doc.rect(0.0, 0.0, size, size, &svg::Style::fill("white"));
+
+    let path: Vec<(f64, f64)> = cells.iter().map(|&(coord, _)| center(&coord)).collect();
+    doc.polyline(&path, &svg::Style::stroke("lightsteelblue", 4.0));
+
+    for (idx, &(coord, value)) in cells.iter().enumerate() {
+        let (cx, cy) = center(&coord);
+        let distance = coord.manhattan_distance(&Coord { x: 0, y: 0 }) as usize;
+        doc.rect(cx - CELL / 2.0 + 2.0, cy - CELL / 2.0 + 2.0, CELL - 4.0, CELL - 4.0,
+                 &svg::Style::fill(&svg::palette_color(distance)).with_stroke("black", 1.0));
+        doc.text(cx, cy - CELL / 4.0, 10.0, &format!("{}", idx + 1), "black");
+        doc.text(cx, cy + CELL / 8.0, 12.0, &format!("{}", value), "white");
+    }
+
+    doc
+}
+
+fn debug(csys: &CoordinateSystem, n: u64) {
+    println!("{}: {:?} d: {}", n, csys.coords_of(n), csys.coords_of(n).manhattan_distance(&Coord { x: 0, y: 0 }));
+}
+
+pub fn run() {
+    let csys = CoordinateSystem::new();
+    debug(&csys, 1);
+    debug(&csys, 12);
+    debug(&csys, 23);
+    debug(&csys, 1024);
+    debug(&csys, 277678);
+    println!("Day 3 result 1: {}", csys.coords_of(277678).manhattan_distance(&Coord { x: 0, y: 0 }));
+    println!("Day 3 result 2: {}", find_first_larger_cell(&csys, 277678));
+    println!("Day 3 u64::MAX is at {:?}", csys.coords_of(u64::MAX));
+    let turned = CoordinateSystem::with_layout(Direction::Up, Rotation::Clockwise, YAxis::Up);
+    println!("Day 3 squares 2..5 starting up and turning clockwise with y up: {:?}",
+             (2..6).map(|n| turned.coords_of(n)).collect::<Vec<Coord>>());
+
+    let (last, _, largest) = StressTest::new(&csys, Neighbourhood::Eight, Fold::sum()).last().unwrap();
+    println!("Day 3 stress test fits in u128 up to square {} with {}", last, largest);
+    let (n, _, value) = StressTest::new(&csys, Neighbourhood::Four, Fold::sum()).find(|&(_, _, value)| value >= 277678).unwrap();
+    println!("Day 3 stress test with 4 neighbours: square {} is the first with {}", n, value);
+    let values: Vec<u128> = StressTest::new(&csys, Neighbourhood::Eight, Fold::product_mod(1000003)).with_seed(2).take(10).map(|(_, _, value)| value).collect();
+    println!("Day 3 stress test with products mod 1000003: {:?}", values);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::testing::Lcg;
+
+    // Reference implementation walking the rings one by one
+    fn walk_coords_of(n: u64) -> Coord {
         assert_ne!(n, 0);
         let mut from = 2;
         let mut level = 1;
@@ -68,8 +353,8 @@ impl CoordinateSystem {
                 if n < next {
                     let idx = n - from;
                     let segment = idx  / (width - 1);
-                    let pos = (idx % (width - 1)) as i32;
-                    let l = level as i32;
+                    let pos = (idx % (width - 1)) as i64;
+                    let l = level as i64;
 
                     result =
                         match segment {
@@ -90,47 +375,71 @@ impl CoordinateSystem {
 
         result
     }
-}
 
-fn calc_value(squares: &HashMap<Coord, u32>, coord: &Coord) -> u32 {
-    squares.get(&coord.left()).unwrap_or(&0) +
-    squares.get(&coord.right()).unwrap_or(&0) +
-    squares.get(&coord.up()).unwrap_or(&0) +
-    squares.get(&coord.down()).unwrap_or(&0) +
-    squares.get(&coord.left().up()).unwrap_or(&0) +
-    squares.get(&coord.right().up()).unwrap_or(&0) +
-    squares.get(&coord.left().down()).unwrap_or(&0) +
-    squares.get(&coord.right().down()).unwrap_or(&0)
-}
+    fn all_layouts() -> Vec<CoordinateSystem> {
+        let mut result = Vec::new();
+        for &start in [Direction::Right, Direction::Up, Direction::Left, Direction::Down].iter() {
+            for &rotation in [Rotation::CounterClockwise, Rotation::Clockwise].iter() {
+                for &y_axis in [YAxis::Down, YAxis::Up].iter() {
+                    result.push(CoordinateSystem::with_layout(start, rotation, y_axis));
+                }
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn conversions_match_ring_walk() {
+        let csys = CoordinateSystem::new();
+        for n in 1..100000 {
+            let coord = csys.coords_of(n);
+            assert_eq!(coord, walk_coords_of(n));
+            assert_eq!(csys.index_of(&coord), Some(n));
+        }
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        let csys = CoordinateSystem::new();
+        let mut rng = Lcg::new(42);
+        let mut samples: Vec<u64> = vec![u64::MAX, u64::MAX - 1, 1 << 63, (1 << 32) * (1 << 31), 4294967295 * 4294967295];
+        for _ in 0..100000 {
+            samples.push(cmp::max(rng.next_u64(), 1));
+        }
+        for n in samples {
+            let coord = csys.coords_of(n);
+            assert_eq!(csys.index_of(&coord), Some(n), "{} -> {:?}", n, coord);
+        }
 
-fn find_first_larger_cell(csys: &mut CoordinateSystem, than: u32) -> u32 {
-    let mut squares: HashMap<Coord, u32> = HashMap::new();
-    let mut n: u32 = 1;
-    let mut coord: Coord = csys.coords_of(n);
-    let mut value: u32 = 1;
-    squares.insert(coord, value);
+        assert_eq!(csys.index_of(&Coord { x: 1 << 31, y: 1 << 31 }), None);
+    }
 
-    while value < than {
-        n = n + 1;
-        coord = csys.coords_of(n);
-        value = calc_value(&squares, &coord);
-        squares.insert(coord, value);
+    #[test]
+    fn far_away_coords_are_out_of_range() {
+        let far = [i64::MIN, i64::MIN + 1, i64::MAX, -(1 << 32), 1 << 32, (1 << 31) + 1, -(1 << 31) - 1];
+        for csys in all_layouts() {
+            for &v in far.iter() {
+                assert_eq!(csys.index_of(&Coord { x: v, y: 0 }), None);
+                assert_eq!(csys.index_of(&Coord { x: 0, y: v }), None);
+                assert_eq!(csys.index_of(&Coord { x: v, y: v }), None);
+            }
+        }
     }
 
-    value
-}
+    // Every layout is an isometry of the default one, so distances and the stress test values must not change
+    #[test]
+    fn layouts_are_isometries() {
+        let default = CoordinateSystem::new();
+        let origin = Coord { x: 0, y: 0 };
+        let expected_value = find_first_larger_cell(&default, 277678);
 
-fn debug(csys: &mut CoordinateSystem, n: u32) {
-    println!("{}: {:?} d: {}", n, csys.coords_of(n), csys.coords_of(n).manhattan_distance(&Coord { x: 0, y: 0 }));
+        for csys in all_layouts() {
+            for n in 1..10000 {
+                let coord = csys.coords_of(n);
+                assert_eq!(csys.index_of(&coord), Some(n));
+                assert_eq!(coord.manhattan_distance(&origin), default.coords_of(n).manhattan_distance(&origin));
+            }
+            assert_eq!(find_first_larger_cell(&csys, 277678), expected_value);
+        }
+    }
 }
-
-pub fn run() {
-    let mut csys = CoordinateSystem::new();
-    debug(&mut csys, 1);
-    debug(&mut csys, 12);
-    debug(&mut csys, 23);
-    debug(&mut csys, 1024);
-    debug(&mut csys, 277678);
-    println!("Day 3 result 1: {}", csys.coords_of(277678).manhattan_distance(&Coord { x: 0, y: 0 }));
-    println!("Day 3 result 2: {}", find_first_larger_cell(&mut csys, 277678));
-}
\ No newline at end of file
@@ -1,14 +1,10 @@
-use std::collections::HashSet;
+extern crate unicode_normalization;
+
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::iter::FromIterator;
 
 use common;
-
-fn is_valid_1(s: &str) -> bool {
-    let all_words: Vec<&str> = s.split(' ').collect();
-    let unique_words: HashSet<&str> = HashSet::from_iter(s.split(' '));
-
-    unique_words.len() == all_words.len()
-}
+use self::unicode_normalization::UnicodeNormalization;
 
 fn sorted_chars_of(s: &str) -> String {
     let mut chars: Vec<char> = s.chars().into_iter().collect();
@@ -17,21 +13,418 @@ fn sorted_chars_of(s: &str) -> String {
     String::from_iter(chars.iter())
 }
 
-fn is_valid_2(s: &str) -> bool {
-    let all_words: Vec<&str> = s.split(' ').collect();
-    let unique_words: HashSet<String> =
-        HashSet::from_iter(all_words.iter().map(|s| sorted_chars_of(*s)));
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Normalisation {
+    Nfc,
+    CaseFold,
+    SortChars
+}
+
+impl Normalisation {
+    fn apply(&self, word: &str) -> String {
+        match *self {
+            Normalisation::Nfc => word.nfc().collect(),
+            Normalisation::CaseFold => word.to_lowercase(),
+            Normalisation::SortChars => sorted_chars_of(word)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Failure {
+    reason: String,
+    groups: Vec<Vec<String>>
+}
+
+fn unique_reason(normalisations: &[Normalisation]) -> String {
+    match normalisations {
+        [] => String::from("duplicate words"),
+        [Normalisation::SortChars] => String::from("anagrams"),
+        [Normalisation::CaseFold] => String::from("case-insensitive duplicates"),
+        [Normalisation::Nfc] => String::from("nfc duplicates"),
+        _ => {
+            let names: Vec<String> = normalisations.iter().map(|n| format!("{:?}", n).to_lowercase()).collect();
+            format!("duplicates by {}", names.join("+"))
+        }
+    }
+}
+
+// Groups of words sharing the same key, in the order of their first appearance
+fn colliding_groups(words: &[&str], normalisations: &[Normalisation]) -> Vec<Vec<String>> {
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut groups: Vec<Vec<String>> = Vec::new();
+    for &word in words {
+        let key = normalisations.iter().fold(String::from(word), |acc, normalisation| normalisation.apply(&acc));
+        let idx = *positions.entry(key).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[idx].push(String::from(word));
+    }
+
+    groups.into_iter().filter(|group| group.len() > 1).collect()
+}
+
+#[derive(Clone, Debug)]
+enum Rule {
+    // No two words may be equal after applying the normalisations in order
+    UniqueBy(Vec<Normalisation>),
+    MinWordCount(usize),
+    BannedWords(HashSet<String>),
+    All(Vec<Rule>),
+    Any(Vec<Rule>)
+}
+
+impl Rule {
+    pub fn unique_words() -> Rule {
+        Rule::UniqueBy(vec![])
+    }
+
+    pub fn no_anagrams() -> Rule {
+        Rule::UniqueBy(vec![Normalisation::SortChars])
+    }
+
+    pub fn case_insensitive_unique() -> Rule {
+        Rule::UniqueBy(vec![Normalisation::CaseFold])
+    }
+
+    pub fn nfc_unique() -> Rule {
+        Rule::UniqueBy(vec![Normalisation::Nfc])
+    }
+
+    pub fn banned_words(words: &[&str]) -> Rule {
+        Rule::BannedWords(words.iter().map(|&word| String::from(word)).collect())
+    }
+
+    pub fn and(self, other: Rule) -> Rule {
+        match self {
+            Rule::All(mut rules) => {
+                rules.push(other);
+                Rule::All(rules)
+            },
+            rule => Rule::All(vec![rule, other])
+        }
+    }
+
+    pub fn or(self, other: Rule) -> Rule {
+        match self {
+            Rule::Any(mut rules) => {
+                rules.push(other);
+                Rule::Any(rules)
+            },
+            rule => Rule::Any(vec![rule, other])
+        }
+    }
+
+    pub fn check(&self, words: &[&str]) -> bool {
+        match *self {
+            Rule::UniqueBy(ref normalisations) => {
+                let keys: HashSet<String> = words.iter()
+                    .map(|&word| normalisations.iter().fold(String::from(word), |acc, normalisation| normalisation.apply(&acc)))
+                    .collect();
+                keys.len() == words.len()
+            },
+            Rule::MinWordCount(count) => words.len() >= count,
+            Rule::BannedWords(ref banned) => words.iter().all(|&word| !banned.contains(word)),
+            Rule::All(ref rules) => rules.iter().all(|rule| rule.check(words)),
+            Rule::Any(ref rules) => rules.iter().any(|rule| rule.check(words))
+        }
+    }
+
+    // Every reason why the words violate the rule, empty if they are valid
+    pub fn diagnose(&self, words: &[&str]) -> Vec<Failure> {
+        match *self {
+            Rule::UniqueBy(ref normalisations) => {
+                let groups = colliding_groups(words, normalisations);
+                if groups.is_empty() {
+                    vec![]
+                } else {
+                    vec![Failure { reason: unique_reason(normalisations), groups }]
+                }
+            },
+            Rule::MinWordCount(count) =>
+                if words.len() >= count {
+                    vec![]
+                } else {
+                    vec![Failure { reason: format!("fewer than {} words", count), groups: vec![] }]
+                },
+            Rule::BannedWords(ref banned) => {
+                let found: Vec<String> = words.iter().filter(|&word| banned.contains(*word)).map(|&word| String::from(word)).collect();
+                if found.is_empty() {
+                    vec![]
+                } else {
+                    vec![Failure { reason: String::from("banned words"), groups: vec![found] }]
+                }
+            },
+            Rule::All(ref rules) => rules.iter().flat_map(|rule| rule.diagnose(words)).collect(),
+            Rule::Any(ref rules) => {
+                let failures: Vec<Vec<Failure>> = rules.iter().map(|rule| rule.diagnose(words)).collect();
+                if failures.iter().any(|f| f.is_empty()) {
+                    vec![]
+                } else {
+                    failures.into_iter().flatten().collect()
+                }
+            }
+        }
+    }
+
+    pub fn is_valid(&self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        self.check(&words)
+    }
+}
+
+fn preset(name: &str) -> Option<Rule> {
+    match name {
+        "part1" => Some(Rule::unique_words()),
+        "part2" => Some(Rule::no_anagrams()),
+        _ => None
+    }
+}
+
+struct LineReport {
+    line_number: usize,
+    failures: Vec<Failure>
+}
+
+fn diagnose_lines(lines: &[&str], rule: &Rule) -> Vec<LineReport> {
+    lines.iter()
+        .enumerate()
+        .map(|(idx, line)| {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            LineReport { line_number: idx + 1, failures: rule.diagnose(&words) }
+        })
+        .filter(|report| !report.failures.is_empty())
+        .collect()
+}
 
-    unique_words.len() == all_words.len()
+fn failure_histogram(reports: &[LineReport]) -> BTreeMap<String, usize> {
+    let mut histogram: BTreeMap<String, usize> = BTreeMap::new();
+    for failure in reports.iter().flat_map(|report| report.failures.iter()) {
+        *histogram.entry(failure.reason.clone()).or_insert(0) += 1;
+    }
+    histogram
 }
 
-pub fn run() {
+fn text_report(reports: &[LineReport]) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for report in reports {
+        for failure in report.failures.iter() {
+            let groups: Vec<String> = failure.groups.iter().map(|group| format!("[{}]", group.join(" "))).collect();
+            lines.push(format!("line {}: {} {}", report.line_number, failure.reason, groups.join(" ")).trim_end().to_string());
+        }
+    }
+    lines.push(String::from("summary:"));
+    for (reason, count) in failure_histogram(reports) {
+        lines.push(format!("  {}: {}", reason, count));
+    }
+    lines.join("\n")
+}
+
+fn json_string(s: &str) -> String {
+    let mut result = String::from("\"");
+    for ch in s.chars() {
+        match ch {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            ch if (ch as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => result.push(ch)
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn json_report(reports: &[LineReport]) -> String {
+    let line_strs: Vec<String> = reports.iter().map(|report| {
+        let failure_strs: Vec<String> = report.failures.iter().map(|failure| {
+            let group_strs: Vec<String> = failure.groups.iter()
+                .map(|group| format!("[{}]", group.iter().map(|word| json_string(word)).collect::<Vec<String>>().join(",")))
+                .collect();
+            format!("{{\"reason\":{},\"groups\":[{}]}}", json_string(&failure.reason), group_strs.join(","))
+        }).collect();
+        format!("{{\"line\":{},\"failures\":[{}]}}", report.line_number, failure_strs.join(","))
+    }).collect();
+    let summary_strs: Vec<String> = failure_histogram(reports).iter()
+        .map(|(reason, count)| format!("{}:{}", json_string(reason), count))
+        .collect();
+
+    format!("{{\"lines\":[{}],\"summary\":{{{}}}}}", line_strs.join(","), summary_strs.join(","))
+}
+
+fn count_valid(lines: &[&str], rule: &Rule) -> usize {
+    lines.iter().filter(|&line| rule.is_valid(line)).count()
+}
+
+fn example() {
+    let strict = Rule::nfc_unique()
+        .and(Rule::case_insensitive_unique())
+        .and(Rule::MinWordCount(3))
+        .and(Rule::banned_words(&["password", "secret"]).or(Rule::MinWordCount(6)));
+
+    for &line in ["abc Abc def", "caf\u{e9} cafe\u{301} xyz", "abc def password", "ab cd ef gh ij password", "abc def"].iter() {
+        println!("Day 4 example {:?} is valid: {}", line, strict.is_valid(line));
+    }
+}
+
+// Options: --report text|json prints why each line fails the --policy part1|part2 preset (part1 by default)
+pub fn run(options: &[String]) {
     let contents = common::read_data("day4.txt");
 
     let all_lines: Vec<&str> = contents.split("\n").collect();
-    let valid_lines_1: Vec<&&str> = all_lines.iter().filter(|&s|is_valid_1(s)).collect();
-    let valid_lines_2: Vec<&&str> = all_lines.iter().filter(|&s|is_valid_2(s)).collect();
 
-    println!("Day 4 answer 1: {}", valid_lines_1.len());
-    println!("Day 4 answer 2: {}", valid_lines_2.len());
+    if let Some(format) = common::option_value(options, "--report") {
+        let render: fn(&[LineReport]) -> String = match format {
+            "text" => text_report,
+            "json" => json_report,
+            _ => {
+                eprintln!("Unknown report format: {}", format);
+                return;
+            }
+        };
+        let policy = common::option_value(options, "--policy").unwrap_or("part1");
+        match preset(policy) {
+            Some(rule) => println!("{}", render(&diagnose_lines(&all_lines, &rule))),
+            None => eprintln!("Unknown policy: {}", policy)
+        }
+        return;
+    }
+
+    example();
+    println!("Day 4 answer 1: {}", count_valid(&all_lines, &preset("part1").unwrap()));
+    println!("Day 4 answer 2: {}", count_valid(&all_lines, &preset("part2").unwrap()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets() {
+        assert!(preset("part1").unwrap().is_valid("aa bb cc dd ee"));
+        assert!(!preset("part1").unwrap().is_valid("aa bb cc dd aa"));
+        assert!(preset("part1").unwrap().is_valid("abcde xyz ecdab"));
+        assert!(!preset("part2").unwrap().is_valid("abcde xyz ecdab"));
+        assert!(preset("part2").unwrap().is_valid("iiii oiii ooii oooi oooo"));
+        assert!(preset("part3").is_none());
+    }
+
+    #[test]
+    fn and_requires_every_rule() {
+        let rule = Rule::unique_words().and(Rule::MinWordCount(3));
+        assert!(rule.is_valid("a b c"));
+        assert!(!rule.is_valid("a b"));
+        assert!(!rule.is_valid("a b a"));
+
+        // Chaining keeps a single flat list of rules
+        let rule = rule.and(Rule::banned_words(&["x"]));
+        match rule {
+            Rule::All(ref rules) => assert_eq!(rules.len(), 3),
+            ref other => panic!("expected All, got {:?}", other)
+        }
+        assert!(!rule.is_valid("a b x"));
+    }
+
+    #[test]
+    fn or_requires_any_rule() {
+        let rule = Rule::banned_words(&["x"]).or(Rule::MinWordCount(4));
+        assert!(rule.is_valid("a b"));
+        assert!(rule.is_valid("a b c x"));
+        assert!(!rule.is_valid("a b x"));
+
+        let rule = rule.or(Rule::unique_words());
+        match rule {
+            Rule::Any(ref rules) => assert_eq!(rules.len(), 3),
+            ref other => panic!("expected Any, got {:?}", other)
+        }
+        assert!(rule.is_valid("a b x"));
+        assert!(!rule.is_valid("a x x"));
+    }
+
+    #[test]
+    fn nfc_duplicates() {
+        // Precomposed and decomposed forms of the same word
+        let line = "caf\u{e9} cafe\u{301} xyz";
+        assert!(Rule::unique_words().is_valid(line));
+        assert!(!Rule::nfc_unique().is_valid(line));
+        assert!(Rule::nfc_unique().is_valid("caf\u{e9} cafe xyz"));
+    }
+
+    #[test]
+    fn case_folded_duplicates() {
+        assert!(Rule::unique_words().is_valid("abc Abc def"));
+        assert!(!Rule::case_insensitive_unique().is_valid("abc Abc def"));
+        assert!(!Rule::case_insensitive_unique().is_valid("\u{c9}t\u{e9} \u{e9}t\u{e9}"));
+        assert!(Rule::case_insensitive_unique().is_valid("abc abd"));
+    }
+
+    #[test]
+    fn combined_normalisations() {
+        let rule = Rule::UniqueBy(vec![Normalisation::Nfc, Normalisation::CaseFold]);
+        assert!(!rule.is_valid("CAF\u{c9} cafe\u{301}"));
+        assert!(Rule::nfc_unique().is_valid("CAF\u{c9} cafe\u{301}"));
+        assert!(Rule::case_insensitive_unique().is_valid("CAF\u{c9} cafe\u{301}"));
+    }
+
+    fn words(line: &str) -> Vec<&str> {
+        line.split_whitespace().collect()
+    }
+
+    fn groups(lists: &[&[&str]]) -> Vec<Vec<String>> {
+        lists.iter().map(|list| list.iter().map(|&word| String::from(word)).collect()).collect()
+    }
+
+    #[test]
+    fn colliding_groups_keep_the_first_appearance_order() {
+        let line = words("b a c b a d a");
+        assert_eq!(colliding_groups(&line, &[]), groups(&[&["b", "b"], &["a", "a", "a"]]));
+
+        let line = words("xyz ab ba zyx yxz cd");
+        assert_eq!(colliding_groups(&line, &[Normalisation::SortChars]),
+                   groups(&[&["xyz", "zyx", "yxz"], &["ab", "ba"]]));
+        assert!(colliding_groups(&words("ab cd ef"), &[]).is_empty());
+    }
+
+    #[test]
+    fn any_is_silent_when_one_branch_passes() {
+        let rule = Rule::banned_words(&["x"]).or(Rule::MinWordCount(4));
+        assert_eq!(rule.diagnose(&words("a b")), vec![]);
+        assert_eq!(rule.diagnose(&words("a b c x")), vec![]);
+        assert_eq!(rule.diagnose(&words("a b x")), vec![
+            Failure { reason: String::from("banned words"), groups: groups(&[&["x"]]) },
+            Failure { reason: String::from("fewer than 4 words"), groups: vec![] }
+        ]);
+    }
+
+    #[test]
+    fn all_reports_every_failure() {
+        let rule = Rule::unique_words().and(Rule::no_anagrams()).and(Rule::MinWordCount(2));
+        let reasons: Vec<String> = rule.diagnose(&words("ab ab")).into_iter().map(|failure| failure.reason).collect();
+        assert_eq!(reasons, vec!["duplicate words", "anagrams"]);
+        assert_eq!(rule.diagnose(&words("ab")), vec![
+            Failure { reason: String::from("fewer than 2 words"), groups: vec![] }
+        ]);
+    }
+
+    #[test]
+    fn json_string_escapes() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(json_string("a\"b"), "\"a\\\"b\"");
+        assert_eq!(json_string("a\\b"), "\"a\\\\b\"");
+        assert_eq!(json_string("a\nb\tc\u{1}"), "\"a\\u000ab\\u0009c\\u0001\"");
+        assert_eq!(json_string("caf\u{e9}"), "\"caf\u{e9}\"");
+    }
+
+    #[test]
+    fn reports() {
+        let lines = ["aa bb", "aa aa bb bb", "cc cc"];
+        let reports = diagnose_lines(&lines, &Rule::unique_words());
+        assert_eq!(text_report(&reports),
+                   "line 2: duplicate words [aa aa] [bb bb]\nline 3: duplicate words [cc cc]\nsummary:\n  duplicate words: 2");
+        assert_eq!(json_report(&reports),
+                   "{\"lines\":[{\"line\":2,\"failures\":[{\"reason\":\"duplicate words\",\"groups\":[[\"aa\",\"aa\"],[\"bb\",\"bb\"]]}]},\
+                    {\"line\":3,\"failures\":[{\"reason\":\"duplicate words\",\"groups\":[[\"cc\",\"cc\"]]}]}],\
+                    \"summary\":{\"duplicate words\":2}}");
+    }
+
 }
@@ -1,9 +1,317 @@
+extern crate flate2;
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::time::Instant;
+
 use common;
+use self::flate2::Compression;
+use self::flate2::read::GzDecoder;
+use self::flate2::write::GzEncoder;
+
+#[derive(Debug)]
+enum Error {
+    CouldNotParseRule(String),
+    InvalidRecording(String),
+    UnknownFormat(String),
+    CouldNotRead(io::Error),
+    CouldNotWrite(io::Error)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::CouldNotParseRule(ref rule) => write!(f, "could not parse modifier rule {:?}", rule),
+            Error::InvalidRecording(ref reason) => write!(f, "invalid recording: {}", reason),
+            Error::UnknownFormat(ref format) => write!(f, "unknown recording format {}", format),
+            Error::CouldNotRead(ref err) => write!(f, "could not read recording: {}", err),
+            Error::CouldNotWrite(ref err) => write!(f, "could not write recording: {}", err)
+        }
+    }
+}
+
+// Above this many cells comparing the whole state on every step costs too much, so loops are
+// not detected and only the step limit stops a non-halting program
+const LOOP_DETECTION_MAX_CELLS: usize = 64;
+
+// Under modifier2 a cell that reached 2 or 3 flips between the two forever, so a prefix
+// of such cells can be stored as bits and crossed a whole block at a time
+const BLOCK_BITS: usize = 3;
+const BLOCK: usize = 1 << BLOCK_BITS;
+
+// Indexed by (block bits << BLOCK_BITS) | entry offset, packs the new bits, the steps taken and the exit offset
+fn settled_block_table() -> Vec<u32> {
+    let mut table = vec![0u32; (1 << BLOCK) * BLOCK];
+    for bits in 0..(1usize << BLOCK) {
+        for entry in 0..BLOCK {
+            let mut new_bits = bits;
+            let mut offset = entry;
+            let mut steps = 0;
+            while offset < BLOCK {
+                let jump = if new_bits & (1 << offset) != 0 { 3 } else { 2 };
+                new_bits ^= 1 << offset;
+                offset += jump;
+                steps += 1;
+            }
+            table[(bits << BLOCK_BITS) | entry] = (new_bits as u32) | (steps << 16) | ((offset as u32) << 24);
+        }
+    }
+
+    table
+}
+
+fn is_settled(offset: i32) -> bool {
+    offset == 2 || offset == 3
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Comparison {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual
+}
+
+impl Comparison {
+    fn parse(s: &str) -> Option<Comparison> {
+        match s {
+            "<" => Some(Comparison::Less),
+            "<=" => Some(Comparison::LessOrEqual),
+            ">" => Some(Comparison::Greater),
+            ">=" => Some(Comparison::GreaterOrEqual),
+            "==" => Some(Comparison::Equal),
+            "!=" => Some(Comparison::NotEqual),
+            _ => None
+        }
+    }
+
+    fn holds(&self, a: i32, b: i32) -> bool {
+        match *self {
+            Comparison::Less => a < b,
+            Comparison::LessOrEqual => a <= b,
+            Comparison::Greater => a > b,
+            Comparison::GreaterOrEqual => a >= b,
+            Comparison::Equal => a == b,
+            Comparison::NotEqual => a != b
+        }
+    }
+}
+
+// Modifier rules of the form "+1" or "if offset >= 3 then -1 else +1", where the else branch may be another rule
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum ModifierRule {
+    Constant(i32),
+    If {
+        comparison: Comparison,
+        value: i32,
+        then: i32,
+        otherwise: Box<ModifierRule>
+    }
+}
+
+impl ModifierRule {
+    fn parse(s: &str) -> Result<ModifierRule, Error> {
+        let tokens: Vec<&str> = s.split_whitespace().collect();
+        ModifierRule::parse_tokens(&tokens).ok_or_else(|| Error::CouldNotParseRule(String::from(s)))
+    }
+
+    fn parse_tokens(tokens: &[&str]) -> Option<ModifierRule> {
+        match tokens {
+            [constant] => constant.parse::<i32>().ok().map(ModifierRule::Constant),
+            ["if", "offset", comparison, value, "then", then, "else", otherwise @ ..] =>
+                Some(ModifierRule::If {
+                    comparison: Comparison::parse(comparison)?,
+                    value: value.parse::<i32>().ok()?,
+                    then: then.parse::<i32>().ok()?,
+                    otherwise: Box::new(ModifierRule::parse_tokens(otherwise)?)
+                }),
+            _ => None
+        }
+    }
+
+    fn apply(&self, offset: i32) -> i32 {
+        match *self {
+            ModifierRule::Constant(change) => change,
+            ModifierRule::If { comparison, value, then, ref otherwise } =>
+                if comparison.holds(offset, value) {
+                    then
+                } else {
+                    otherwise.apply(offset)
+                }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Outcome {
+    Halted(u64),
+    // The state after `start` steps is seen again after every `period` further steps
+    Looping { start: u64, period: u64 },
+    // The step after these many would take a cell's offset out of the i32 range
+    Overflowed(u64),
+    LimitReached
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Step {
+    Jumped,
+    Outside,
+    Overflow
+}
+
+// One step on a bare state, so that loop detection can replay the program without touching the machine.
+// The position is an i64, a jump from any cell by any offset stays in its range
+fn advance(jumps: &mut [i32], position: &mut i64, modifier: &dyn Fn(i32) -> i32) -> Step {
+    if *position < 0 || *position >= jumps.len() as i64 {
+        return Step::Outside;
+    }
+
+    let cell = *position as usize;
+    let offset = jumps[cell];
+    match offset.checked_add(modifier(offset)) {
+        Some(changed) => {
+            jumps[cell] = changed;
+            *position += offset as i64;
+            Step::Jumped
+        },
+        None => Step::Overflow
+    }
+}
+
+impl Outcome {
+    fn steps(&self) -> Option<u64> {
+        match *self {
+            Outcome::Halted(steps) => Some(steps),
+            _ => None
+        }
+    }
+}
+
+const RECORDING_MAGIC: &[u8; 4] = b"AOC5";
+
+#[derive(Debug, PartialEq, Eq)]
+struct Recording {
+    // The position before each step, so its length is the number of steps
+    positions: Vec<u32>,
+    visits: Vec<u64>,
+    final_offsets: Vec<i32>
+}
+
+fn read_u32<R: Read>(input: &mut R) -> Result<u32, Error> {
+    let mut buf = [0u8; 4];
+    input.read_exact(&mut buf).map_err(Error::CouldNotRead)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(input: &mut R) -> Result<u64, Error> {
+    let mut buf = [0u8; 8];
+    input.read_exact(&mut buf).map_err(Error::CouldNotRead)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+impl Recording {
+    fn new(cells: usize) -> Self {
+        Recording {
+            positions: Vec::new(),
+            visits: vec![0; cells],
+            final_offsets: Vec::new()
+        }
+    }
+
+    fn record(&mut self, position: usize) {
+        self.positions.push(position as u32);
+        self.visits[position] += 1;
+    }
+
+    // The most visited cells with their share of all the steps
+    fn hot_cells(&self, count: usize) -> Vec<(usize, u64, f64)> {
+        let mut cells: Vec<(usize, u64)> = self.visits.iter().cloned().enumerate().collect();
+        cells.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let total = self.positions.len().max(1) as f64;
+        cells.into_iter().take(count).map(|(cell, visits)| (cell, visits, visits as f64 / total)).collect()
+    }
+
+    fn write_trace_csv<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "step,position")?;
+        for (step, position) in self.positions.iter().enumerate() {
+            writeln!(out, "{},{}", step, position)?;
+        }
+        Ok(())
+    }
+
+    fn write_cells_csv<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "cell,visits,final_offset")?;
+        for (cell, (visits, offset)) in self.visits.iter().zip(self.final_offsets.iter()).enumerate() {
+            writeln!(out, "{},{},{}", cell, visits, offset)?;
+        }
+        Ok(())
+    }
+
+    // Gzipped little endian: magic, cell count, visits and final offset per cell, step count,
+    // then the trace as the difference between consecutive positions, which are mostly small
+    fn write_binary<W: Write>(&self, out: W) -> io::Result<()> {
+        let mut encoder = io::BufWriter::new(GzEncoder::new(out, Compression::fast()));
+        encoder.write_all(RECORDING_MAGIC)?;
+        encoder.write_all(&(self.visits.len() as u32).to_le_bytes())?;
+        for (visits, offset) in self.visits.iter().zip(self.final_offsets.iter()) {
+            encoder.write_all(&visits.to_le_bytes())?;
+            encoder.write_all(&offset.to_le_bytes())?;
+        }
+        encoder.write_all(&(self.positions.len() as u64).to_le_bytes())?;
+        let mut previous: u32 = 0;
+        for &position in self.positions.iter() {
+            encoder.write_all(&position.wrapping_sub(previous).to_le_bytes())?;
+            previous = position;
+        }
+        encoder.into_inner().map_err(|err| err.into_error())?.finish()?;
+        Ok(())
+    }
+
+    // Only the tests read recordings back so far
+    #[allow(dead_code)]
+    fn read_binary<R: Read>(input: R) -> Result<Recording, Error> {
+        let mut decoder = io::BufReader::new(GzDecoder::new(input));
+        let mut magic = [0u8; 4];
+        decoder.read_exact(&mut magic).map_err(Error::CouldNotRead)?;
+        if &magic != RECORDING_MAGIC {
+            return Err(Error::InvalidRecording(String::from("bad magic")));
+        }
+
+        // The counts come from the file, so the vectors only grow with the data actually read
+        let cells = read_u32(&mut decoder)? as usize;
+        let mut visits = Vec::new();
+        let mut final_offsets = Vec::new();
+        for _ in 0..cells {
+            visits.push(read_u64(&mut decoder)?);
+            final_offsets.push(read_u32(&mut decoder)? as i32);
+        }
+
+        let steps = read_u64(&mut decoder)?;
+        let mut positions = Vec::new();
+        let mut previous: u32 = 0;
+        for _ in 0..steps {
+            previous = previous.wrapping_add(read_u32(&mut decoder)?);
+            if previous as usize >= cells {
+                return Err(Error::InvalidRecording(format!("position {} outside of {} cells", previous, cells)));
+            }
+            positions.push(previous);
+        }
+
+        Ok(Recording { positions, visits, final_offsets })
+    }
+}
 
 struct Machine {
     jumps: Vec<i32>,
-    position: i32,
-    modifier: fn(i32) -> i32
+    position: i64,
+    modifier: Box<dyn Fn(i32) -> i32>,
+    // Only set by `part2`, the block skipping of `run_fast` relies on the modifier being modifier2
+    settling: bool,
+    recording: Option<Recording>
 }
 
 fn modifier1(_offset: i32) -> i32 {
@@ -19,34 +327,157 @@ fn modifier2(offset: i32) -> i32 {
 }
 
 impl Machine {
-    fn new(initial_offsets: Vec<i32>, modifier: fn(i32) -> i32) -> Self {
+    fn new<F: Fn(i32) -> i32 + 'static>(initial_offsets: Vec<i32>, modifier: F) -> Self {
         Machine {
             jumps: initial_offsets,
             position: 0,
-            modifier
+            modifier: Box::new(modifier),
+            settling: false,
+            recording: None
+        }
+    }
+
+    fn part2(initial_offsets: Vec<i32>) -> Self {
+        Machine { settling: true, ..Machine::new(initial_offsets, modifier2) }
+    }
+
+    fn recorded(self) -> Self {
+        let recording = Recording::new(self.jumps.len());
+        Machine { recording: Some(recording), ..self }
+    }
+
+    // The recording so far, with the current offsets as the final ones
+    fn take_recording(&mut self) -> Option<Recording> {
+        let mut recording = self.recording.take()?;
+        recording.final_offsets = self.jumps.clone();
+        Some(recording)
+    }
+
+    fn with_rule(initial_offsets: Vec<i32>, rule: &str) -> Result<Self, Error> {
+        let rule = ModifierRule::parse(rule)?;
+        Ok(Machine::new(initial_offsets, move |offset| rule.apply(offset)))
+    }
+
+    fn step(&mut self) -> Step {
+        let position = self.position;
+        let step = advance(&mut self.jumps, &mut self.position, &*self.modifier);
+        if step == Step::Jumped {
+            if let Some(ref mut recording) = self.recording {
+                recording.record(position as usize);
+            }
+        }
+
+        step
+    }
+
+    // Runs until the program jumps outside, at most `limit` steps if given. Loops are found with
+    // Brent's algorithm as in `common::cycle::brent`, so only the initial and one saved state are kept,
+    // and a loop may only be noticed up to about twice its start and period steps after entering it
+    fn run(&mut self, limit: Option<u64>) -> Outcome {
+        let detect_loops = self.jumps.len() <= LOOP_DETECTION_MAX_CELLS;
+        let initial = (self.position, self.jumps.clone());
+        let mut saved = initial.clone();
+        let mut power: u64 = 1;
+        let mut distance: u64 = 0;
+        let mut steps: u64 = 0;
+
+        loop {
+            if limit.is_some_and(|limit| steps >= limit) {
+                return Outcome::LimitReached;
+            }
+            match self.step() {
+                Step::Jumped => steps += 1,
+                Step::Outside => return Outcome::Halted(steps),
+                Step::Overflow => return Outcome::Overflowed(steps)
+            }
+
+            if detect_loops {
+                distance += 1;
+                if self.position == saved.0 && self.jumps == saved.1 {
+                    return Outcome::Looping { start: self.loop_start(initial, distance), period: distance };
+                }
+                if distance == power {
+                    saved = (self.position, self.jumps.clone());
+                    power *= 2;
+                    distance = 0;
+                }
+            }
         }
     }
 
-    fn step(&mut self) -> bool {
-        if self.position >= 0 && self.position < (self.jumps.len() as i32) {
-            let position: usize = self.position as usize;
-            let offset: i32 = self.jumps[position];
-            self.jumps[position] += (self.modifier)(offset);
-            self.position += offset;
+    // The first step of a loop of the given period, found by replaying the program from its initial state
+    // with a second copy `period` steps ahead, until both are in the same state
+    fn loop_start(&self, initial: (i64, Vec<i32>), period: u64) -> u64 {
+        let (mut position, mut jumps) = initial;
+        let (mut ahead_position, mut ahead_jumps) = (position, jumps.clone());
+        for _ in 0..period {
+            advance(&mut ahead_jumps, &mut ahead_position, &*self.modifier);
+        }
 
-            true
-        } else {
-            false
+        let mut start = 0;
+        while position != ahead_position || jumps != ahead_jumps {
+            advance(&mut jumps, &mut position, &*self.modifier);
+            advance(&mut ahead_jumps, &mut ahead_position, &*self.modifier);
+            start += 1;
         }
+
+        start
     }
 
-    fn run(&mut self) -> u32 {
-        let mut steps: u32 = 0;
-        while self.step() {
-            steps += 1;
+    // Same outcome and final state as `run(None)`. Settled blocks are only skipped for machines created by
+    // `part2`, any other modifier or a recorded machine has to visit every step, so it falls back to `run`
+    fn run_fast(&mut self) -> Outcome {
+        if self.recording.is_some() || !self.settling {
+            return self.run(None);
+        }
+
+        let table = settled_block_table();
+        let mut settled: Vec<u32> = Vec::new();
+        let mut steps: u64 = 0;
+
+        while self.position >= 0 && self.position < (self.jumps.len() as i64) {
+            let position = self.position as usize;
+            let block = position / BLOCK;
+            if block < settled.len() {
+                let limit = settled.len() * BLOCK;
+                let mut position = position;
+                while position < limit {
+                    let block = position >> BLOCK_BITS;
+                    let packed = table[((settled[block] as usize) << BLOCK_BITS) | (position & (BLOCK - 1))];
+                    settled[block] = packed & 0xffff;
+                    steps += ((packed >> 16) & 0xff) as u64;
+                    position = (block << BLOCK_BITS) + (packed >> 24) as usize;
+                }
+                self.position = position as i64;
+            } else {
+                // Under modifier2 an offset only moves towards 2 and 3, so the step can not overflow
+                self.step();
+                steps += 1;
+
+                // Only a change in the first unsettled block can extend the settled prefix
+                if block == settled.len() {
+                    while (settled.len() + 1) * BLOCK <= self.jumps.len() {
+                        let start = settled.len() * BLOCK;
+                        let cells = &self.jumps[start..start + BLOCK];
+                        if !cells.iter().all(|&offset| is_settled(offset)) {
+                            break;
+                        }
+                        let bits = cells.iter().enumerate()
+                            .filter(|&(_, &offset)| offset == 3)
+                            .fold(0u32, |acc, (idx, _)| acc | (1 << idx));
+                        settled.push(bits);
+                    }
+                }
+            }
         }
 
-        steps
+        for (block, &bits) in settled.iter().enumerate() {
+            for idx in 0..BLOCK {
+                self.jumps[block * BLOCK + idx] = if bits & (1 << idx) != 0 { 3 } else { 2 };
+            }
+        }
+
+        Outcome::Halted(steps)
     }
 
     #[allow(dead_code)]
@@ -62,7 +493,12 @@ impl Machine {
 
 fn example() {
     let mut machine = Machine::new(vec![0, 3, 0, 1, -3], modifier1);
-    println!("Day 5 example result 1: {}", machine.run());
+    println!("Day 5 example result 1: {}", machine.run(None).steps().unwrap());
+
+    let mut bouncing = Machine::with_rule(vec![2, 0, -2], "if offset == 0 then 0 else if offset > 0 then -1 else +1").unwrap();
+    println!("Day 5 example bouncing: {:?}", bouncing.run(None));
+    let mut stuck = Machine::new(vec![0; 100], |_| 0);
+    println!("Day 5 example stuck: {:?}", stuck.run(Some(1000)));
 }
 
 fn read_data() -> Vec<i32> {
@@ -75,18 +511,214 @@ fn part1() {
     let initial_offsets = read_data();
     let mut machine = Machine::new(initial_offsets, modifier1);
 
-    println!("Day 5 result 1: {}", machine.run());
+    println!("Day 5 result 1: {}", machine.run(None).steps().unwrap());
 }
 
-fn part2() {
+// The stepping runner only runs for --bench, the tests check that both give the same result
+fn part2(bench: bool) {
     let initial_offsets = read_data();
-    let mut machine = Machine::new(initial_offsets, modifier2);
 
-    println!("Day 5 result 2: {}", machine.run());
+    let start = Instant::now();
+    let result = Machine::part2(initial_offsets.clone()).run_fast().steps().unwrap();
+    let fast_time = start.elapsed();
+
+    println!("Day 5 result 2: {}", result);
+    if bench {
+        let start = Instant::now();
+        let stepping_result = Machine::new(initial_offsets, modifier2).run(None).steps().unwrap();
+        let stepping_time = start.elapsed();
+        println!("Day 5 part 2 stepping: {:?} ({} steps), fast-forward: {:?}", stepping_time, stepping_result, fast_time);
+    }
+}
+
+fn save_recording(recording: &Recording, prefix: &str, format: &str) -> Result<Vec<String>, Error> {
+    match format {
+        "csv" => {
+            let trace_path = format!("{}.trace.csv", prefix);
+            let cells_path = format!("{}.cells.csv", prefix);
+            File::create(&trace_path).and_then(|file| recording.write_trace_csv(&mut io::BufWriter::new(file))).map_err(Error::CouldNotWrite)?;
+            File::create(&cells_path).and_then(|file| recording.write_cells_csv(&mut io::BufWriter::new(file))).map_err(Error::CouldNotWrite)?;
+            Ok(vec![trace_path, cells_path])
+        },
+        "binary" => {
+            let path = format!("{}.bin.gz", prefix);
+            File::create(&path).and_then(|file| recording.write_binary(io::BufWriter::new(file))).map_err(Error::CouldNotWrite)?;
+            Ok(vec![path])
+        },
+        _ => Err(Error::UnknownFormat(String::from(format)))
+    }
+}
+
+fn record_parts(prefix: &str, format: &str) {
+    if format != "csv" && format != "binary" {
+        eprintln!("{}", Error::UnknownFormat(String::from(format)));
+        return;
+    }
+
+    for &(name, modifier) in [("part1", modifier1 as fn(i32) -> i32), ("part2", modifier2)].iter() {
+        let mut machine = Machine::new(read_data(), modifier).recorded();
+        let outcome = machine.run(None);
+        let recording = machine.take_recording().unwrap();
+
+        println!("Day 5 {} recorded: {:?}", name, outcome);
+        for (cell, visits, share) in recording.hot_cells(5) {
+            println!("  cell {}: {} visits ({:.1}%)", cell, visits, share * 100.0);
+        }
+        match save_recording(&recording, &format!("{}.{}", prefix, name), format) {
+            Ok(paths) => println!("  saved to {}", paths.join(", ")),
+            Err(err) => eprintln!("  {}", err)
+        }
+    }
 }
 
-pub fn run() {
+// Options: --bench prints the running time of the stepping and the fast-forward runners,
+// --record <prefix> [--format csv|binary] saves the trace and the per-cell statistics of both parts instead
+pub fn run(options: &[String]) {
+    if let Some(prefix) = common::option_value(options, "--record") {
+        record_parts(prefix, common::option_value(options, "--format").unwrap_or("csv"));
+        return;
+    }
+
     example();
     part1();
-    part2();
-}
\ No newline at end of file
+    part2(options.iter().any(|option| option == "--bench"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::cycle;
+    use common::testing::Lcg;
+
+    // Jumps back further and further, so the first blocks settle at 2 and 3 and are crossed many times
+    fn offsets() -> Vec<i32> {
+        (0..200).map(|idx| -(idx / 3)).collect()
+    }
+
+    #[test]
+    fn puzzle_examples() {
+        assert_eq!(Machine::new(vec![0, 3, 0, 1, -3], modifier1).run(None), Outcome::Halted(5));
+        assert_eq!(Machine::part2(vec![0, 3, 0, 1, -3]).run_fast(), Outcome::Halted(10));
+
+        let rule = "if offset >= 3 then -1 else +1";
+        assert_eq!(Machine::with_rule(vec![0, 3, 0, 1, -3], rule).unwrap().run(None), Outcome::Halted(10));
+        assert!(ModifierRule::parse("if offset >= 3 then -1").is_err());
+    }
+
+    #[test]
+    fn run_fast_matches_run_on_the_input() {
+        let mut machine = Machine::new(read_data(), modifier2);
+        let mut fast_machine = Machine::part2(read_data());
+        assert_eq!(machine.run(None), fast_machine.run_fast());
+        assert_eq!(machine.jumps, fast_machine.jumps);
+        assert_eq!(machine.position, fast_machine.position);
+    }
+
+    #[test]
+    fn run_fast_matches_run() {
+        let mut machine = Machine::part2(offsets());
+        let mut fast_machine = Machine::part2(offsets());
+        assert_eq!(machine.run(None), fast_machine.run_fast());
+        assert_eq!(machine.jumps, fast_machine.jumps);
+        assert_eq!(machine.position, fast_machine.position);
+    }
+
+    fn bouncing(offset: i32) -> i32 {
+        if offset == 0 { 0 } else if offset > 0 { -1 } else { 1 }
+    }
+
+    // Every looping program must agree with a search storing all the states
+    #[test]
+    fn loops_match_hashing() {
+        let mut rng = Lcg::new(5);
+        let mut loops = 0;
+        for _ in 0..500 {
+            let cells = 1 + rng.below(8) as usize;
+            let offsets: Vec<i32> = (0..cells).map(|_| rng.below(7) as i32 - 3).collect();
+            let outcome = Machine::new(offsets.clone(), bouncing).run(Some(100000));
+            if let Outcome::Looping { start, period } = outcome {
+                let step = |state: &(i64, Vec<i32>)| {
+                    let (mut position, mut jumps) = state.clone();
+                    advance(&mut jumps, &mut position, &bouncing);
+                    (position, jumps)
+                };
+                assert_eq!(cycle::hashing(step, &(0, offsets.clone())), (start as usize, period as usize), "{:?}", offsets);
+                loops += 1;
+            }
+        }
+        assert!(loops > 50);
+    }
+
+    #[test]
+    fn overflowing_offsets_stop_the_program() {
+        let mut machine = Machine::with_rule(vec![0, 0], "+2000000000").unwrap();
+        assert_eq!(machine.run(None), Outcome::Overflowed(1));
+        assert_eq!(machine.jumps, vec![2000000000, 0]);
+        assert_eq!(machine.position, 0);
+
+        // Far jumps leave the program instead of wrapping the position
+        let mut machine = Machine::new(vec![1, i32::MAX], |_| -1);
+        assert_eq!(machine.run(None), Outcome::Halted(2));
+        assert_eq!(machine.position, 1 + i32::MAX as i64);
+    }
+
+    fn example_recording() -> Recording {
+        let mut machine = Machine::new(vec![0, 3, 0, 1, -3], modifier1).recorded();
+        machine.run(None);
+        machine.take_recording().unwrap()
+    }
+
+    #[test]
+    fn binary_recording_round_trip() {
+        let recording = example_recording();
+        assert_eq!(recording.positions, vec![0, 0, 1, 4, 1]);
+        assert_eq!(recording.final_offsets, vec![2, 5, 0, 1, -2]);
+        assert_eq!(recording.hot_cells(2), vec![(0, 2, 0.4), (1, 2, 0.4)]);
+
+        let mut binary: Vec<u8> = Vec::new();
+        recording.write_binary(&mut binary).unwrap();
+        assert_eq!(Recording::read_binary(binary.as_slice()).unwrap(), recording);
+    }
+
+    #[test]
+    fn csv_recording() {
+        let recording = example_recording();
+        let mut trace: Vec<u8> = Vec::new();
+        recording.write_trace_csv(&mut trace).unwrap();
+        assert_eq!(String::from_utf8(trace).unwrap(), "step,position\n0,0\n1,0\n2,1\n3,4\n4,1\n");
+        let mut cells: Vec<u8> = Vec::new();
+        recording.write_cells_csv(&mut cells).unwrap();
+        assert_eq!(String::from_utf8(cells).unwrap(), "cell,visits,final_offset\n0,2,2\n1,2,5\n2,0,0\n3,0,1\n4,1,-2\n");
+    }
+
+    #[test]
+    fn truncated_recording_is_a_read_error() {
+        let mut binary: Vec<u8> = Vec::new();
+        example_recording().write_binary(&mut binary).unwrap();
+        match Recording::read_binary(&binary[..binary.len() / 2]) {
+            Err(Error::CouldNotRead(_)) => {},
+            other => panic!("unexpected {:?}", other)
+        }
+
+        // A header claiming u32::MAX cells must not be trusted for allocation
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(RECORDING_MAGIC).unwrap();
+        encoder.write_all(&u32::MAX.to_le_bytes()).unwrap();
+        match Recording::read_binary(encoder.finish().unwrap().as_slice()) {
+            Err(Error::CouldNotRead(ref err)) if err.kind() == io::ErrorKind::UnexpectedEof => {},
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    // Machines not created by `part2` are stepped, cells at 2 and 3 stop flipping with the last modifier
+    #[test]
+    fn run_fast_steps_other_modifiers() {
+        let modifiers: Vec<fn(i32) -> i32> = vec![modifier1, modifier2, |offset| if offset >= 2 { 0 } else { 1 }];
+        for &modifier in modifiers.iter() {
+            let mut machine = Machine::new(offsets(), modifier);
+            let mut fast_machine = Machine::new(offsets(), modifier);
+            assert_eq!(machine.run(None), fast_machine.run_fast());
+            assert_eq!(machine.jumps, fast_machine.jumps);
+        }
+    }
+}
@@ -1,8 +1,10 @@
-use std::collections::HashSet;
+use std::mem;
+
+use common::cycle;
 
 #[derive(Clone, Debug, Eq, Hash)]
 struct State {
-    banks: Vec<u32>
+    banks: Vec<u64>
 }
 
 impl PartialEq for State {
@@ -12,13 +14,14 @@ impl PartialEq for State {
 }
 
 impl State {
-    fn new(banks: Vec<u32>) -> Self {
+    fn new(banks: Vec<u64>) -> Self {
         State { banks }
     }
 
-    fn find_max(&self) -> (usize, u32) {
-        let (idx, max) = self.banks.iter().enumerate().min_by_key(|&(_, &value)| -(value as i32)).unwrap();
-        (idx, max.clone())
+    // The first bank with the most blocks
+    fn find_max(&self) -> (usize, u64) {
+        let (idx, max) = self.banks.iter().enumerate().max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(&a.0))).unwrap();
+        (idx, *max)
     }
 
     fn redistribute(&mut self) {
@@ -26,14 +29,15 @@ impl State {
 
         let (start, max) = self.find_max();
         let len = self.banks.len();
-        let mut remaining = max;
-        let mut idx = start;
+        let share = max / len as u64;
+        let extra = (max % len as u64) as usize;
 
         self.banks[start] = 0;
-        while remaining > 0 {
-            idx = (idx + 1) % len;
-            remaining = remaining - 1;
-            self.banks[idx] = self.banks[idx] + 1;
+        for blocks in self.banks.iter_mut() {
+            *blocks += share;
+        }
+        for offset in 1..(extra + 1) {
+            self.banks[(start + offset) % len] += 1;
         }
     }
 
@@ -46,43 +50,143 @@ impl State {
     }
 }
 
-fn count_redistribution_cycles(initial_state: &State, with_cycle_length: bool) -> (u32, Option<u32>) {
-    let mut visited_states: HashSet<State> = HashSet::new();
+fn redistributed(state: &State) -> State {
+    let mut next = state.clone();
+    next.redistribute();
+    next
+}
 
-    let mut state = initial_state.clone();
-    let mut count = 0;
-    while !visited_states.contains(&state) {
-        visited_states.insert(state.clone());
-        state.redistribute();
-        count = count + 1;
+fn count_redistribution_cycles(initial_state: &State) -> (usize, usize) {
+    let (mu, lambda) = cycle::hashing(redistributed, initial_state);
+    (mu + lambda, lambda)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CycleMode {
+    Hashing,
+    Brent
+}
+
+#[derive(Debug)]
+struct CycleReport {
+    loop_start: usize,
+    loop_length: usize,
+    stored_states: usize,
+    // The stored states and their bookkeeping, without the hash table's own slack
+    memory_bytes: usize
+}
+
+fn detect_cycle(initial_state: &State, mode: CycleMode) -> CycleReport {
+    let state_bytes = mem::size_of::<State>() + initial_state.banks.len() * mem::size_of::<u64>();
+    match mode {
+        CycleMode::Hashing => {
+            let (mu, lambda) = cycle::hashing(redistributed, initial_state);
+            // Every state before the repetition is kept, together with its index
+            let stored_states = mu + lambda;
+            CycleReport {
+                loop_start: mu,
+                loop_length: lambda,
+                stored_states,
+                memory_bytes: stored_states * (state_bytes + mem::size_of::<usize>())
+            }
+        },
+        CycleMode::Brent => {
+            let (mu, lambda) = cycle::brent(redistributed, initial_state);
+            // Only the tortoise and the hare
+            CycleReport {
+                loop_start: mu,
+                loop_length: lambda,
+                stored_states: 2,
+                memory_bytes: 2 * state_bytes
+            }
+        }
     }
+}
 
-    let cycle_length =
-        if with_cycle_length {
-            let (l, _) = count_redistribution_cycles(&state, false);
-            Some(l)
-        } else {
-            None
-        };
-    (count, cycle_length)
+fn print_cycle_reports(name: &str, initial_state: &State) {
+    for &mode in [CycleMode::Hashing, CycleMode::Brent].iter() {
+        let report = detect_cycle(initial_state, mode);
+        println!("{} {:?}: loop starts at {}, length {}, {} states stored ({} bytes)",
+                 name, mode, report.loop_start, report.loop_length, report.stored_states, report.memory_bytes);
+    }
 }
 
 fn example() {
     let initial_state = State::new(vec![0, 2, 7, 0]);
-    println!("Example results: {:?}", count_redistribution_cycles(&initial_state, true));
+    println!("Example results: {:?}", count_redistribution_cycles(&initial_state));
+    print_cycle_reports("Example", &initial_state);
 }
 
 fn puzzle_input() -> State {
     let input = "4	10	4	1	8	4	9	14	5	1	14	15	0	15	3	5";
-    return State::new(input.split_whitespace().map(|s| s.trim().parse::<u32>().unwrap()).collect());
+    return State::new(input.split_whitespace().map(|s| s.trim().parse::<u64>().unwrap()).collect());
 }
 
 fn part1() {
     let initial_state = puzzle_input();
-    println!("Day 6 results: {:?}", count_redistribution_cycles(&initial_state, true));
+    println!("Day 6 results: {:?}", count_redistribution_cycles(&initial_state));
+    print_cycle_reports("Day 6", &initial_state);
 }
 
 pub fn run() {
     example();
     part1();
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Reference implementation moving the blocks one by one
+    fn redistribute_one_by_one(state: &mut State) {
+        let (start, max) = state.find_max();
+        let len = state.banks.len();
+        let mut remaining = max;
+        let mut idx = start;
+
+        state.banks[start] = 0;
+        while remaining > 0 {
+            idx = (idx + 1) % len;
+            remaining = remaining - 1;
+            state.banks[idx] = state.banks[idx] + 1;
+        }
+    }
+
+    #[test]
+    fn redistribution_matches_one_by_one() {
+        let mut reference = State::new(vec![0, 2, 7, 0]);
+        let mut state = reference.clone();
+        for _ in 0..10 {
+            state.redistribute();
+            redistribute_one_by_one(&mut reference);
+            assert_eq!(state, reference);
+        }
+    }
+
+    #[test]
+    fn large_banks_are_redistributed() {
+        let mut state = State::new(vec![3, 1 << 33, 17, 1 << 33, 5, 0, 40]);
+        state.redistribute();
+        assert_eq!(state.banks, vec![1227133516, 1227133513, 1227133531, (1 << 33) + 1227133513, 1227133518, 1227133513, 1227133553]);
+    }
+
+    #[test]
+    fn example_cycles() {
+        assert_eq!(count_redistribution_cycles(&State::new(vec![0, 2, 7, 0])), (5, 4));
+    }
+
+    #[test]
+    fn brent_matches_hashing() {
+        let initial_states = vec![
+            State::new(vec![0, 2, 7, 0]),
+            State::new(vec![0]),
+            State::new(vec![3, 0, 0]),
+            puzzle_input()
+        ];
+        for initial_state in initial_states.iter() {
+            let hashing = detect_cycle(initial_state, CycleMode::Hashing);
+            let brent = detect_cycle(initial_state, CycleMode::Brent);
+            assert_eq!((brent.loop_start, brent.loop_length), (hashing.loop_start, hashing.loop_length),
+                       "{:?}", initial_state);
+        }
+    }
+}
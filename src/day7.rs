@@ -2,7 +2,59 @@ extern crate regex;
 
 use common;
 use self::regex::Regex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Problem {
+    Unparsable(usize),
+    DuplicateName { name: String, line: usize, first_line: usize },
+    MissingChild { parent: String, child: String, line: usize },
+    // The lines of every program listing the child
+    MultipleParents { child: String, parent_lines: Vec<(String, usize)> },
+    NoRoot,
+    MultipleRoots(Vec<(String, usize)>),
+    Cycle(Vec<(String, usize)>)
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn names_with_lines(items: &[(String, usize)]) -> String {
+            items.iter().map(|&(ref name, line)| format!("{} (line {})", name, line)).collect::<Vec<String>>().join(", ")
+        }
+
+        match *self {
+            Problem::Unparsable(line) => write!(f, "line {}: could not parse", line),
+            Problem::DuplicateName { ref name, line, first_line } => write!(f, "line {}: {} is already defined on line {}", line, name, first_line),
+            Problem::MissingChild { ref parent, ref child, line } => write!(f, "line {}: {} has unknown child {}", line, parent, child),
+            Problem::MultipleParents { ref child, ref parent_lines } => write!(f, "{} has multiple parents: {}", child, names_with_lines(parent_lines)),
+            Problem::NoRoot => write!(f, "there is no root program"),
+            Problem::MultipleRoots(ref roots) => write!(f, "multiple roots: {}", names_with_lines(roots)),
+            Problem::Cycle(ref programs) => write!(f, "cycle: {}", names_with_lines(programs))
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Error {
+    InvalidTower(Vec<Problem>)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidTower(ref problems) => {
+                write!(f, "invalid tower:")?;
+                for problem in problems {
+                    write!(f, "\n  {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 struct Program {
@@ -13,14 +65,14 @@ struct Program {
 }
 
 impl Program {
-    pub fn from_line(line: &str) -> Self {
+    pub fn from_line(line: &str) -> Option<Self> {
         lazy_static! {
-            static ref RE: Regex = Regex::new(r#"([a-z]+) \((\d+)\)( -> ([a-z, ]+))?"#).unwrap();
+            static ref RE: Regex = Regex::new(r#"^([a-z]+) \((\d+)\)( -> ([a-z, ]+))?$"#).unwrap();
         }
 
-        let captures = RE.captures(line).unwrap();
-        let name = captures.get(1).unwrap().as_str();
-        let weigth = captures.get(2).unwrap().as_str().parse::<i32>().unwrap();
+        let captures = RE.captures(line.trim())?;
+        let name = captures.get(1)?.as_str();
+        let weigth = captures.get(2)?.as_str().parse::<i32>().ok()?;
         let child_list = captures.get(4).map_or("", |m| m.as_str());
         let child_names: Vec<String> = child_list
             .split(',')
@@ -29,23 +81,163 @@ impl Program {
             .map(|s| String::from(s))
             .collect();
 
-        Program {
+        Some(Program {
             name: String::from(name),
             weight: weigth,
             parent: None,
             children: child_names
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Colour {
+    // On the current path
+    Grey,
+    // Fully explored
+    Black
+}
+
+// Depth-first search over the children, recording every cycle closed on the current path.
+// Unvisited (white) programs are the ones missing from `colours`
+fn find_cycles<'a>(children: &HashMap<&'a str, &'a [String]>, starts: &[&'a str]) -> Vec<Vec<&'a str>> {
+    let mut colours: HashMap<&str, Colour> = HashMap::new();
+    let mut cycles: Vec<Vec<&str>> = Vec::new();
+
+    for &start in starts {
+        if colours.contains_key(start) {
+            continue;
+        }
+
+        // The path with the index of the next child to follow from each program on it
+        let mut path: Vec<(&str, usize)> = vec![(start, 0)];
+        let mut path_index: HashMap<&str, usize> = HashMap::new();
+        path_index.insert(start, 0);
+        colours.insert(start, Colour::Grey);
+
+        while let Some(&mut (name, ref mut next_child)) = path.last_mut() {
+            let names = children.get(name).cloned().unwrap_or(&[]);
+            let found = names.iter().enumerate().skip(*next_child).find(|&(_, child)| children.contains_key(child.as_str()));
+            let child = match found {
+                Some((idx, child)) => {
+                    *next_child = idx + 1;
+                    child.as_str()
+                },
+                None => {
+                    path.pop();
+                    path_index.remove(name);
+                    colours.insert(name, Colour::Black);
+                    continue;
+                }
+            };
+
+            match colours.get(child) {
+                Some(&Colour::Grey) => cycles.push(path[path_index[child]..].iter().map(|&(name, _)| name).collect()),
+                Some(&Colour::Black) => {},
+                None => {
+                    path_index.insert(child, path.len());
+                    path.push((child, 0));
+                    colours.insert(child, Colour::Grey);
+                }
+            }
         }
     }
+
+    cycles
+}
+
+// Parses the non-empty lines and checks that they form a single tree
+fn validate(input: &str) -> Result<Vec<Program>, Error> {
+    let mut problems: Vec<Problem> = Vec::new();
+    let mut programs: Vec<(usize, Program)> = Vec::new();
+    for (idx, line) in input.split('\n').enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match Program::from_line(line) {
+            Some(program) => programs.push((idx + 1, program)),
+            None => problems.push(Problem::Unparsable(idx + 1))
+        }
+    }
+
+    let mut lines: HashMap<&str, usize> = HashMap::new();
+    for &(line, ref program) in programs.iter() {
+        match lines.get(program.name.as_str()) {
+            Some(&first_line) => problems.push(Problem::DuplicateName { name: program.name.clone(), line, first_line }),
+            None => { lines.insert(program.name.as_str(), line); }
+        }
+    }
+
+    let mut parents: HashMap<&str, Vec<(String, usize)>> = HashMap::new();
+    for &(line, ref program) in programs.iter() {
+        for child in program.children.iter() {
+            if lines.contains_key(child.as_str()) {
+                parents.entry(child.as_str()).or_default().push((program.name.clone(), line));
+            } else {
+                problems.push(Problem::MissingChild { parent: program.name.clone(), child: child.clone(), line });
+            }
+        }
+    }
+
+    let mut multiple_parents: Vec<Problem> = parents.iter()
+        .filter(|&(_, parent_lines)| parent_lines.len() > 1)
+        .map(|(&child, parent_lines)| Problem::MultipleParents { child: String::from(child), parent_lines: parent_lines.clone() })
+        .collect();
+    multiple_parents.sort_by_key(|problem| match *problem {
+        Problem::MultipleParents { ref parent_lines, .. } => parent_lines[1].1,
+        _ => 0
+    });
+    problems.extend(multiple_parents);
+
+    let roots: Vec<(String, usize)> = programs.iter()
+        .filter(|&&(line, ref program)| lines[program.name.as_str()] == line && !parents.contains_key(program.name.as_str()))
+        .map(|&(line, ref program)| (program.name.clone(), line))
+        .collect();
+    if roots.is_empty() && !programs.is_empty() {
+        problems.push(Problem::NoRoot);
+    } else if roots.len() > 1 {
+        problems.push(Problem::MultipleRoots(roots));
+    }
+
+    let children: HashMap<&str, &[String]> = programs.iter()
+        .filter(|&&(line, ref program)| lines[program.name.as_str()] == line)
+        .map(|(_, program)| (program.name.as_str(), program.children.as_slice()))
+        .collect();
+    let starts: Vec<&str> = programs.iter().map(|(_, program)| program.name.as_str()).collect();
+    for cycle in find_cycles(&children, &starts) {
+        problems.push(Problem::Cycle(cycle.iter().map(|&name| (String::from(name), lines[name])).collect()));
+    }
+
+    if problems.is_empty() {
+        Ok(programs.into_iter().map(|(_, program)| program).collect())
+    } else {
+        Err(Error::InvalidTower(problems))
+    }
 }
 
 struct Tree {
-    programs: HashMap<String, Program>
+    programs: HashMap<String, Program>,
+    // Total weight of each program's subtree, wider than the weights so that sums can not overflow
+    totals: HashMap<String, i64>,
+    // Programs whose children's subtrees differ in weight
+    unbalanced: HashSet<String>
 }
 
 #[derive(Clone, Debug)]
+struct UpdateReport {
+    program: String,
+    weight: i32,
+    // None if there is no such program
+    previous_weight: Option<i32>,
+    corrections: Vec<Correction>
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 struct Correction {
     program: String,
-    correction: i32
+    correction: i32,
+    // From the root down to the corrected program
+    path: Vec<String>
 }
 
 fn create_map(programs: &Vec<Program>) -> HashMap<String, Program> {
@@ -59,22 +251,35 @@ fn create_map(programs: &Vec<Program>) -> HashMap<String, Program> {
     result
 }
 
-fn find_outlier(weights: &Vec<i32>) -> (i32, i32) {
-    let mut counts: HashMap<i32, i32> = HashMap::new();
-    for &weight in weights {
-        let count = counts.entry(weight).or_insert(0).clone();
-        counts.insert(weight, count + 1);
-    }
-
-    let mut pairs: Vec<(&i32, &i32)> = counts.iter().collect();
-    pairs.sort_by_key(|&(_, &c)| -c);
+// Subtree weights in a single post-order pass; unknown children count as zero and
+// a cycle is cut where it closes, so malformed input cannot loop forever
+fn compute_totals(programs: &HashMap<String, Program>) -> HashMap<String, i64> {
+    let mut totals: HashMap<String, i64> = HashMap::new();
+    let mut visited: HashSet<&str> = HashSet::new();
 
-    assert_eq!(pairs.len(), 2);
+    for name in programs.keys() {
+        if !visited.insert(name.as_str()) {
+            continue;
+        }
 
-    let (wgood, _) = pairs[0];
-    let (wbad, _) = pairs[1];
+        let mut stack: Vec<(&str, bool)> = vec![(name.as_str(), false)];
+        while let Some((current, expanded)) = stack.pop() {
+            let program = &programs[current];
+            if expanded {
+                let child_weights: i64 = program.children.iter().map(|child| totals.get(child).cloned().unwrap_or(0)).sum();
+                totals.insert(String::from(current), program.weight as i64 + child_weights);
+            } else {
+                stack.push((current, true));
+                for child in program.children.iter() {
+                    if programs.contains_key(child) && visited.insert(child.as_str()) {
+                        stack.push((child.as_str(), false));
+                    }
+                }
+            }
+        }
+    }
 
-    (*wgood, *wbad)
+    totals
 }
 
 impl Tree {
@@ -84,75 +289,611 @@ impl Tree {
         // Setting parent references
         for program in programs {
             for child in program.children.iter() {
-                match result.get_mut(&*child) {
-                    Some(child_program) => child_program.parent = Some(program.name.clone()),
-                    None => eprintln!("Could not find {}", child)
+                if let Some(child_program) = result.get_mut(&*child) {
+                    child_program.parent = Some(program.name.clone());
                 }
             }
         }
 
-        Tree { programs: result }
+        let totals = compute_totals(&result);
+        let mut tree = Tree { programs: result, totals, unbalanced: HashSet::new() };
+        let unbalanced: HashSet<String> = tree.programs.values()
+            .filter(|program| !tree.is_balanced(program))
+            .map(|program| program.name.clone())
+            .collect();
+        tree.unbalanced = unbalanced;
+        tree
     }
 
+    fn is_balanced(&self, program: &Program) -> bool {
+        let weights = self.child_weights(program);
+        weights.iter().all(|&w| w == weights[0])
+    }
+
+    // Changes a program's own weight, updating the cached totals and balance of its
+    // ancestors only. Returns the previous weight, or None if there is no such program
+    pub fn set_weight(&mut self, name: &str, weight: i32) -> Option<i32> {
+        let previous = {
+            let program = self.programs.get_mut(name)?;
+            let previous = program.weight;
+            program.weight = weight;
+            previous
+        };
+
+        let delta = weight as i64 - previous as i64;
+        let mut current = Some(String::from(name));
+        while let Some(name) = current {
+            *self.totals.get_mut(&name).unwrap() += delta;
+            let parent = self.programs[&name].parent.clone();
+            if let Some(ref parent) = parent {
+                if self.is_balanced(&self.programs[parent]) {
+                    self.unbalanced.remove(parent);
+                } else {
+                    self.unbalanced.insert(parent.clone());
+                }
+            }
+            current = parent;
+        }
+
+        Some(previous)
+    }
+
+    // Applies the weight changes one after the other, reporting the corrections after each
+    pub fn apply_updates<I: IntoIterator<Item = (String, i32)>>(&mut self, updates: I) -> Vec<UpdateReport> {
+        let root = match self.root() {
+            Some(root) => root.name.clone(),
+            None => return vec![]
+        };
+
+        updates.into_iter().map(|(program, weight)| {
+            let previous_weight = self.set_weight(&program, weight);
+            let corrections = self.find_corrections(&self.programs[&root]);
+            UpdateReport { program, weight, previous_weight, corrections }
+        }).collect()
+    }
+
+    // The only program without a parent
     pub fn root(&self) -> Option<&Program> {
-        match self.programs.iter().find(|&(_name, program)| program.parent.is_none()) {
-            Some((_name, program)) => Some(program),
-            None => None
+        let mut roots = self.programs.values().filter(|program| program.parent.is_none());
+        match (roots.next(), roots.next()) {
+            (Some(program), None) => Some(program),
+            _ => None
         }
     }
 
-    pub fn total_weight(&self, program: &Program) -> i32 {
-        if program.children.is_empty() {
-            program.weight
-        } else {
-            let child_weights: i32 = program.children.iter().map(|child| self.total_weight(self.programs.get(child).unwrap())).sum();
-            program.weight + child_weights
+    pub fn total_weight(&self, program: &Program) -> i64 {
+        self.totals[&program.name]
+    }
+
+    fn child_weights(&self, program: &Program) -> Vec<i64> {
+        program.children.iter().map(|child| self.programs.get(child).map_or(0, |child| self.total_weight(child))).collect()
+    }
+
+    // The names from the root down to the given program
+    pub fn path_to(&self, name: &str) -> Vec<String> {
+        let mut path = vec![String::from(name)];
+        let mut on_path: HashSet<String> = path.iter().cloned().collect();
+        while let Some(parent) = self.programs.get(path.last().unwrap()).and_then(|program| program.parent.clone()) {
+            if !on_path.insert(parent.clone()) {
+                break;
+            }
+            path.push(parent);
         }
+        path.reverse();
+        path
     }
 
-    pub fn find_correction(&self, program: &Program) -> Option<Correction> {
-        if program.children.is_empty() {
-            None
-        } else {
-            let weights: Vec<i32> = program.children.iter().map(|child| self.total_weight(self.programs.get(child).unwrap())).collect();
-            let children_corrections: Vec<Option<Correction>> = program.children.iter().map(|child| self.find_correction(self.programs.get(child).unwrap())).collect();
-            let children_balanced = children_corrections.iter().all(|c| c.is_none());
-            let weights_good = weights.iter().all(|w| *w == weights[0]);
+    // Every child that would balance the program if it was the only wrong one.
+    // With three or more children this is the single outlier, but with two children
+    // either one can be changed to match the other
+    // `path` leads from the root to the program
+    fn candidates_at(&self, program: &Program, path: &[String]) -> Vec<Correction> {
+        let weights = self.child_weights(program);
+        let mut candidates = Vec::new();
 
-            if children_balanced && weights_good {
-                None
-            } else {
-                if children_balanced && !weights_good {
-                    for idx in 0..weights.len() {
-                        let child = self.programs.get(&program.children[idx]).unwrap();
+        for (idx, child_name) in program.children.iter().enumerate() {
+            let others: Vec<i64> = weights.iter().enumerate().filter(|&(other, _)| other != idx).map(|(_, &w)| w).collect();
+            if let Some(&target) = others.first() {
+                if others.iter().all(|&w| w == target) && weights[idx] != target {
+                    let child = match self.programs.get(child_name) {
+                        Some(child) => child,
+                        None => continue
+                    };
+                    // A weight outside 0..=i32::MAX can not be written back, so it is no correction
+                    let correction = i32::try_from(child.weight as i64 - (weights[idx] - target)).ok().filter(|&w| w >= 0);
+                    if let Some(correction) = correction {
+                        let mut child_path = path.to_vec();
+                        child_path.push(child.name.clone());
+                        candidates.push(Correction {
+                            program: child.name.clone(),
+                            correction,
+                            path: child_path
+                        });
+                    }
+                }
+            }
+        }
 
-                        let (good_weight, wrong_weight) = find_outlier(&weights);
+        candidates
+    }
+
+    // The imbalance is fixed at the deepest unbalanced programs that can be corrected. Only
+    // the unbalanced children are followed down from `program`, depth-first and without recursion
+    pub fn find_corrections(&self, program: &Program) -> Vec<Correction> {
+        // The program, the index of its next child to look at and the corrections found below it
+        let mut stack: Vec<(&Program, usize, Vec<Correction>)> = vec![(program, 0, vec![])];
+        let mut path = self.path_to(&program.name);
 
-                        if weights[idx] == wrong_weight {
-                            let diff = wrong_weight - good_weight;
-                            return Some(Correction { program: child.name.clone(), correction: child.weight - diff });
-                        }
+        loop {
+            let next_child = {
+                let &mut (current, ref mut next_idx, _) = stack.last_mut().unwrap();
+                let found = current.children.iter().enumerate().skip(*next_idx)
+                    .find(|&(_, child)| self.unbalanced.contains(child));
+                match found {
+                    Some((idx, child)) => {
+                        *next_idx = idx + 1;
+                        self.programs.get(child)
+                    },
+                    None => {
+                        *next_idx = current.children.len();
+                        None
                     }
+                }
+            };
 
-                    None
+            if let Some(child) = next_child {
+                path.push(child.name.clone());
+                stack.push((child, 0, vec![]));
+                continue;
+            }
 
+            let (current, _, found) = stack.pop().unwrap();
+            let corrections =
+                if !found.is_empty() {
+                    found
+                } else if self.unbalanced.contains(&current.name) {
+                    self.candidates_at(current, &path)
                 } else {
-                    let cs: Vec<Correction> = children_corrections
-                        .iter().filter(|c| c.is_some())
-                        .map(|c| c.clone().unwrap().clone()).collect();
-                    cs.first().map(|c| c.clone())
+                    vec![]
+                };
+            path.pop();
+
+            match stack.last_mut() {
+                Some(&mut (_, _, ref mut parent_found)) => parent_found.extend(corrections),
+                None => return corrections
+            }
+        }
+    }
+
+    // The correction if there is exactly one candidate
+    pub fn find_correction(&self, program: &Program) -> Option<Correction> {
+        let mut corrections = self.find_corrections(program);
+        if corrections.len() == 1 {
+            corrections.pop()
+        } else {
+            None
+        }
+    }
+
+    // The programs in depth-first order from the root, with their depth
+    fn depth_first(&self) -> Vec<(&Program, usize)> {
+        let mut result = Vec::new();
+        let mut stack: Vec<(&Program, usize)> = self.root().into_iter().map(|root| (root, 0)).collect();
+        while let Some((program, depth)) = stack.pop() {
+            result.push((program, depth));
+            for child in program.children.iter().rev() {
+                if let Some(child) = self.programs.get(child) {
+                    stack.push((child, depth + 1));
                 }
             }
         }
+        result
+    }
+
+    // Graphviz graph with weights and subtree totals; the programs to correct are red
+    // and their siblings, whose totals they should match, are yellow
+    pub fn to_dot(&self) -> String {
+        let corrections = self.root().map_or(vec![], |root| self.find_corrections(root));
+        let mut siblings: HashSet<&str> = HashSet::new();
+        for correction in corrections.iter() {
+            let parent = self.programs[&correction.program].parent.as_ref();
+            for sibling in parent.into_iter().flat_map(|parent| self.programs[parent].children.iter()) {
+                siblings.insert(sibling.as_str());
+            }
+        }
+
+        let mut lines = vec![String::from("digraph tower {"), String::from("  node [shape=box];")];
+        let programs = self.depth_first();
+        for &(program, _) in programs.iter() {
+            let correction = corrections.iter().find(|c| c.program == program.name);
+            let style = match correction {
+                Some(_) => ", style=filled, fillcolor=tomato",
+                None if siblings.contains(program.name.as_str()) => ", style=filled, fillcolor=gold",
+                None => ""
+            };
+            let fix = correction.map_or(String::new(), |c| format!("\\nshould be {}", c.correction));
+            lines.push(format!("  \"{}\" [label=\"{}\\nweight {}\\ntotal {}{}\"{}];",
+                               program.name, program.name, program.weight, self.total_weight(program), fix, style));
+        }
+        for &(program, _) in programs.iter() {
+            for child in program.children.iter() {
+                lines.push(format!("  \"{}\" -> \"{}\";", program.name, child));
+            }
+        }
+        lines.push(String::from("}"));
+
+        lines.join("\n")
+    }
+
+    // One program per line indented by depth; below `max_depth` only the number of hidden children is shown
+    pub fn to_text(&self, max_depth: Option<usize>) -> String {
+        let corrections = self.root().map_or(vec![], |root| self.find_corrections(root));
+        let mut lines = Vec::new();
+        for (program, depth) in self.depth_first() {
+            if max_depth.is_some_and(|max_depth| depth > max_depth) {
+                continue;
+            }
+
+            let mut line = format!("{}{} ({}) total {}", "  ".repeat(depth), program.name, program.weight, self.total_weight(program));
+            if max_depth == Some(depth) && !program.children.is_empty() {
+                line.push_str(&format!(" [+{} children]", program.children.len()));
+            }
+            if let Some(correction) = corrections.iter().find(|c| c.program == program.name) {
+                line.push_str(&format!(" <- should be {}", correction.correction));
+            }
+            lines.push(line);
+        }
+
+        lines.join("\n")
+    }
+}
+
+fn parse_tree(input: &str) -> Result<Tree, Error> {
+    let programs = validate(input)?;
+    Ok(Tree::from_programs(&programs))
+}
+
+const EXAMPLE: &str = "pbga (66)\nxhth (57)\nebii (61)\nhavc (66)\nktlj (57)\nfwft (72) -> ktlj, cntj, xhth\nqoyq (66)\n\
+                       padx (45) -> pbga, havc, qoyq\ntknk (41) -> ugml, padx, fwft\njptl (61)\nugml (68) -> gyxo, ebii, jptl\ngyxo (61)\ncntj (57)";
+
+const BROKEN_EXAMPLE: &str = "a (1) -> b, x\nb (2)\nb (3)\nc (4) -> b\nd (5) -> e\ne (6) -> d\nnot a program";
+
+fn example() {
+    let tree = parse_tree(EXAMPLE).unwrap();
+    let correction = tree.find_correction(tree.root().unwrap()).unwrap();
+    println!("Day 7 example correction: {:?}", correction);
+    println!("{}", tree.to_text(Some(1)));
+
+    let ambiguous = parse_tree("top (10) -> left, right\nleft (5)\nright (7)").unwrap();
+    let corrections = ambiguous.find_corrections(ambiguous.root().unwrap());
+    println!("Day 7 example ambiguous corrections: {:?}", corrections);
+
+    let mut tree = tree;
+    let updates = vec![(String::from("ugml"), 60), (String::from("gyxo"), 70), (String::from("nope"), 1), (String::from("gyxo"), 61)];
+    print_update_reports(&tree.apply_updates(updates));
+
+    if let Err(err) = parse_tree(BROKEN_EXAMPLE) {
+        println!("Day 7 example {}", err);
     }
 }
 
-pub fn run() {
+// Options: --format dot|tree, and --depth N to cut the tree text at depth N
+pub fn export(format: &str, max_depth: Option<usize>) -> Option<String> {
     let input = common::read_data("day7.txt");
-    let programs: Vec<Program> = input.split('\n').map(Program::from_line).collect();
-    let tree = Tree::from_programs(&programs);
+    let tree = match parse_tree(&input) {
+        Ok(tree) => tree,
+        Err(err) => {
+            eprintln!("Day 7 {}", err);
+            return None;
+        }
+    };
+
+    match format {
+        "dot" => Some(tree.to_dot()),
+        "tree" => Some(tree.to_text(max_depth)),
+        _ => {
+            eprintln!("Unknown export format {}, expected dot or tree", format);
+            None
+        }
+    }
+}
+
+// Weights have to be between 0 and i32::MAX like in the puzzle input, other lines are skipped with a message
+fn read_updates(input: &str) -> Vec<(String, i32)> {
+    input.lines()
+        .enumerate()
+        .filter(|&(_, line)| !line.trim().is_empty())
+        .filter_map(|(idx, line)| {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let update = match parts.as_slice() {
+                [name, weight] => match weight.parse::<i32>() {
+                    Ok(weight) if weight >= 0 => Ok((String::from(*name), weight)),
+                    _ => Err(format!("weight {:?} is not between 0 and {}", weight, i32::MAX))
+                },
+                _ => Err(format!("expected a name and a weight, got {:?}", line))
+            };
+            match update {
+                Ok(update) => Some(update),
+                Err(reason) => {
+                    eprintln!("Skipping update on line {}: {}", idx + 1, reason);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn print_update_reports(reports: &[UpdateReport]) {
+    for report in reports {
+        let corrections: Vec<String> = report.corrections.iter().map(|c| format!("{} should be {}", c.path.join(" -> "), c.correction)).collect();
+        match report.previous_weight {
+            None => println!("{}: no such program", report.program),
+            Some(previous) if corrections.is_empty() => println!("{} {} -> {}: balanced", report.program, previous, report.weight),
+            Some(previous) => println!("{} {} -> {}: {}", report.program, previous, report.weight, corrections.join(", "))
+        }
+    }
+}
+
+// Options: --updates <file> applies the "name weight" lines in order and reports the corrections after each
+pub fn run(options: &[String]) {
+    let updates_path = common::option_value(options, "--updates");
+    if updates_path.is_none() {
+        example();
+    }
+
+    let input = common::read_data("day7.txt");
+    let mut tree = match parse_tree(&input) {
+        Ok(tree) => tree,
+        Err(err) => {
+            eprintln!("Day 7 {}", err);
+            return;
+        }
+    };
+
+    if let Some(path) = updates_path {
+        match fs::read_to_string(path) {
+            Ok(updates) => print_update_reports(&tree.apply_updates(read_updates(&updates))),
+            Err(err) => eprintln!("Could not read {}: {}", path, err)
+        }
+        return;
+    }
 
     let root = tree.root().unwrap();
     println!("Day 7 result 1: {:?}", root);
-    println!("Day 7 result 2: {:?}", tree.find_correction(root).map(|c| c.correction));
-}
\ No newline at end of file
+    let correction = tree.find_correction(root);
+    println!("Day 7 result 2: {:?}", correction.as_ref().map(|c| c.correction));
+    if let Some(correction) = correction {
+        println!("Day 7 corrected program: {} at {}", correction.program, correction.path.join(" -> "));
+        tree.set_weight(&correction.program, correction.correction);
+        for remaining in tree.find_corrections(tree.root().unwrap()) {
+            println!("Day 7 still unbalanced after the correction: {} should be {}", remaining.path.join(" -> "), remaining.correction);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corrections_of(tree: &Tree) -> Vec<(String, i32, Vec<String>)> {
+        tree.find_corrections(tree.root().unwrap()).into_iter()
+            .map(|c| (c.program, c.correction, c.path))
+            .collect()
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|&name| String::from(name)).collect()
+    }
+
+    #[test]
+    fn puzzle_example() {
+        let tree = parse_tree(EXAMPLE).unwrap();
+        assert_eq!(tree.root().unwrap().name, "tknk");
+        assert_eq!(tree.total_weight(tree.root().unwrap()), 778);
+        assert_eq!(corrections_of(&tree), vec![(String::from("ugml"), 60, names(&["tknk", "ugml"]))]);
+        assert_eq!(tree.find_correction(tree.root().unwrap()).map(|c| c.correction), Some(60));
+    }
+
+    // With two children either one can be changed, so both are reported and there is no single correction
+    #[test]
+    fn two_children_are_ambiguous() {
+        let tree = parse_tree("top (10) -> left, right\nleft (5)\nright (7)").unwrap();
+        assert_eq!(corrections_of(&tree), vec![
+            (String::from("left"), 7, names(&["top", "left"])),
+            (String::from("right"), 5, names(&["top", "right"]))
+        ]);
+        assert_eq!(tree.find_correction(tree.root().unwrap()), None);
+    }
+
+    // The deepest unbalanced program is corrected, and the path leads to it from the root
+    #[test]
+    fn correction_path_starts_at_the_root() {
+        let tree = parse_tree("root (1) -> a, b, c\na (1) -> d, e, f\nb (6)\nc (6)\nd (1)\ne (1)\nf (2)").unwrap();
+        assert_eq!(corrections_of(&tree), vec![(String::from("f"), 1, names(&["root", "a", "f"]))]);
+        assert_eq!(tree.path_to("f"), names(&["root", "a", "f"]));
+        assert_eq!(tree.path_to("root"), names(&["root"]));
+
+        // Starting below the root still reports the whole path
+        let a = &tree.programs["a"];
+        assert_eq!(tree.find_corrections(a)[0].path, names(&["root", "a", "f"]));
+    }
+
+    fn problems_of(input: &str) -> Vec<Problem> {
+        match validate(input) {
+            Ok(_) => vec![],
+            Err(Error::InvalidTower(problems)) => problems
+        }
+    }
+
+    fn lines(items: &[(&str, usize)]) -> Vec<(String, usize)> {
+        items.iter().map(|&(name, line)| (String::from(name), line)).collect()
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        assert_eq!(problems_of(BROKEN_EXAMPLE), vec![
+            Problem::Unparsable(7),
+            Problem::DuplicateName { name: String::from("b"), line: 3, first_line: 2 },
+            Problem::MissingChild { parent: String::from("a"), child: String::from("x"), line: 1 },
+            Problem::MultipleParents { child: String::from("b"), parent_lines: lines(&[("a", 1), ("c", 4)]) },
+            Problem::MultipleRoots(lines(&[("a", 1), ("c", 4)])),
+            Problem::Cycle(lines(&[("d", 5), ("e", 6)]))
+        ]);
+    }
+
+    #[test]
+    fn towers_without_a_root() {
+        assert_eq!(problems_of("a (1) -> b\nb (2) -> a"), vec![
+            Problem::NoRoot,
+            Problem::Cycle(lines(&[("a", 1), ("b", 2)]))
+        ]);
+        assert_eq!(problems_of("a (1) -> a"), vec![Problem::NoRoot, Problem::Cycle(lines(&[("a", 1)]))]);
+    }
+
+    #[test]
+    fn valid_towers_have_no_problems() {
+        assert_eq!(problems_of(EXAMPLE), vec![]);
+        assert_eq!(problems_of("\n  \nsolo (3)\n"), vec![]);
+        assert_eq!(problems_of(""), vec![]);
+    }
+
+    #[test]
+    fn cycles_below_the_root() {
+        assert_eq!(problems_of("r (1) -> a\na (1) -> b\nb (1) -> c\nc (1) -> a"), vec![
+            Problem::MultipleParents { child: String::from("a"), parent_lines: lines(&[("r", 1), ("c", 4)]) },
+            Problem::Cycle(lines(&[("a", 2), ("b", 3), ("c", 4)]))
+        ]);
+    }
+
+    // A long chain is searched without recursion, and the cycle closing at its end is found once
+    #[test]
+    fn deep_cycles() {
+        let depth = 100000;
+        let names: Vec<String> = (0..depth).map(|idx| format!("p{}", idx)).collect();
+        let mut children: HashMap<&str, &[String]> = HashMap::new();
+        for idx in 0..depth {
+            children.insert(names[idx].as_str(), &names[(idx + 1) % depth..(idx + 1) % depth + 1]);
+        }
+        let cycles = find_cycles(&children, &[names[0].as_str()]);
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].len(), depth);
+        assert_eq!(cycles[0][0], "p0");
+
+        let shared = [String::from("b"), String::from("c")];
+        let mut diamond: HashMap<&str, &[String]> = HashMap::new();
+        diamond.insert("a", &shared);
+        diamond.insert("b", &shared[1..]);
+        diamond.insert("c", &[]);
+        assert!(find_cycles(&diamond, &["a", "b", "c"]).is_empty());
+    }
+
+    // A correction that would need a negative weight is not a candidate
+    #[test]
+    fn negative_corrections_are_skipped() {
+        let tree = parse_tree("top (1) -> a, b, c\na (1)\nb (1)\nc (0) -> d\nd (5)").unwrap();
+        assert!(corrections_of(&tree).is_empty());
+    }
+
+    #[test]
+    fn large_weights_do_not_overflow() {
+        let mut tree = parse_tree("top (1) -> a, b\na (2147483000)\nb (2147483000)").unwrap();
+        assert_eq!(tree.total_weight(tree.root().unwrap()), 4294966001);
+
+        assert_eq!(tree.set_weight("a", i32::MAX), Some(2147483000));
+        assert_eq!(tree.total_weight(tree.root().unwrap()), 4294966648);
+        let corrections = tree.find_corrections(tree.root().unwrap());
+        assert_eq!(corrections.iter().map(|c| c.correction).collect::<Vec<i32>>(), vec![2147483000, i32::MAX]);
+    }
+
+    #[test]
+    fn invalid_updates_are_skipped() {
+        let updates = read_updates("a 5\nb -1\n\nc 2147483648\nd\ne 2147483647 x\nf 2147483647");
+        assert_eq!(updates, vec![(String::from("a"), 5), (String::from("f"), i32::MAX)]);
+    }
+
+    fn update(name: &str, weight: i32) -> (String, i32) {
+        (String::from(name), weight)
+    }
+
+    // The cached totals and balance must match a tree built from scratch with the same weights
+    fn assert_consistent(tree: &Tree) {
+        let programs: Vec<Program> = tree.programs.values().cloned().collect();
+        let rebuilt = Tree::from_programs(&programs);
+        assert_eq!(tree.totals, rebuilt.totals);
+        assert_eq!(tree.unbalanced, rebuilt.unbalanced);
+    }
+
+    #[test]
+    fn updates_flip_the_balance_back() {
+        let mut tree = parse_tree(EXAMPLE).unwrap();
+        let reports = tree.apply_updates(vec![update("ugml", 60), update("gyxo", 70), update("nope", 1), update("gyxo", 61)]);
+        let summary: Vec<_> = reports.iter()
+            .map(|r| (r.previous_weight, r.corrections.iter().map(|c| (c.program.clone(), c.correction)).collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(summary, vec![
+            (Some(68), vec![]),
+            (Some(61), vec![(String::from("gyxo"), 61)]),
+            (None, vec![(String::from("gyxo"), 61)]),
+            (Some(70), vec![])
+        ]);
+        assert_eq!(reports[1].corrections[0].path, names(&["tknk", "ugml", "gyxo"]));
+        assert_eq!(tree.total_weight(tree.root().unwrap()), 770);
+        assert_consistent(&tree);
+
+        // Breaking the balance again and undoing it
+        assert_eq!(tree.set_weight("ugml", 68), Some(60));
+        assert_eq!(tree.find_correction(tree.root().unwrap()).map(|c| c.correction), Some(60));
+        assert_consistent(&tree);
+        assert_eq!(tree.set_weight("ugml", 60), Some(68));
+        assert!(tree.find_corrections(tree.root().unwrap()).is_empty());
+        assert_consistent(&tree);
+    }
+
+    #[test]
+    fn set_weight_updates_the_ancestors_only() {
+        let mut tree = parse_tree(EXAMPLE).unwrap();
+        assert_eq!(tree.set_weight("nope", 5), None);
+        assert_eq!(tree.set_weight("pbga", 70), Some(66));
+        assert_eq!(tree.totals["pbga"], 70);
+        assert_eq!(tree.totals["padx"], 247);
+        assert_eq!(tree.totals["tknk"], 782);
+        assert_eq!(tree.totals["fwft"], 243);
+        assert!(tree.unbalanced.contains("padx"));
+        assert_consistent(&tree);
+    }
+
+    #[test]
+    fn updates_on_a_tower_without_root() {
+        let mut tree = Tree::from_programs(&vec![Program::from_line("a (1)").unwrap(), Program::from_line("b (2)").unwrap()]);
+        assert!(tree.apply_updates(vec![update("a", 3)]).is_empty());
+    }
+
+    #[test]
+    fn text_export() {
+        let tree = parse_tree(EXAMPLE).unwrap();
+        assert_eq!(tree.to_text(Some(0)), "tknk (41) total 778 [+3 children]");
+        assert_eq!(tree.to_text(Some(1)).lines().collect::<Vec<&str>>(), vec![
+            "tknk (41) total 778",
+            "  ugml (68) total 251 [+3 children] <- should be 60",
+            "  padx (45) total 243 [+3 children]",
+            "  fwft (72) total 243 [+3 children]"
+        ]);
+        let full = tree.to_text(None);
+        assert_eq!(full.lines().count(), 13);
+        assert!(full.contains("\n    gyxo (61) total 61\n"));
+    }
+
+    #[test]
+    fn dot_export() {
+        let dot = parse_tree(EXAMPLE).unwrap().to_dot();
+        let lines: Vec<&str> = dot.lines().collect();
+        assert_eq!(lines[0], "digraph tower {");
+        assert_eq!(lines[2], r#"  "tknk" [label="tknk\nweight 41\ntotal 778"];"#);
+        assert!(lines.contains(&r#"  "ugml" [label="ugml\nweight 68\ntotal 251\nshould be 60", style=filled, fillcolor=tomato];"#));
+        assert!(lines.contains(&r#"  "padx" [label="padx\nweight 45\ntotal 243", style=filled, fillcolor=gold];"#));
+        assert!(lines.contains(&r#"  "pbga" [label="pbga\nweight 66\ntotal 66"];"#));
+        assert!(lines.contains(&r#"  "tknk" -> "ugml";"#));
+        assert_eq!(lines.iter().filter(|line| line.contains(" -> ")).count(), 12);
+        assert_eq!(lines[lines.len() - 1], "}");
+    }
+}
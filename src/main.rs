@@ -1,6 +1,8 @@
 #[macro_use] extern crate lazy_static;
 
 use std::env;
+use std::fs::File;
+use std::io::Write;
 
 mod common;
 mod day1;
@@ -24,35 +26,99 @@ mod day18;
 mod day19;
 mod day20;
 
+fn run(day: &str, options: &[String]) {
+    match day {
+        "day1" => day1::run(),
+        "day2" => day2::run(options),
+        "day3" => day3::run(),
+        "day4" => day4::run(options),
+        "day5" => day5::run(options),
+        "day6" => day6::run(),
+        "day7" => day7::run(options),
+        "day8" => day8::run(),
+        "day9" => day9::run(),
+        "day10" => day10::run(),
+        "day11" => day11::run(),
+        "day12" => day12::run(),
+        "day13" => day13::run(),
+        "day14" => day14::run(),
+        "day15" => day15::run(),
+        "day16" => day16::run(),
+        "day17" => day17::run(),
+        "day18" => day18::run(),
+        "day19" => day19::run(),
+        "day20" => day20::run(),
+        _ => eprintln!("{} is not implemented", day)
+    }
+}
+
+fn render(day: &str, options: &[String]) {
+    let tick = common::option_value(options, "--tick").map(|s| s.parse::<usize>().expect("Invalid tick")).unwrap_or(0);
+    let document = match day {
+        "day3" => Some(day3::render()),
+        "day11" => Some(day11::render()),
+        "day13" => Some(day13::render(tick)),
+        "day14" => Some(day14::render()),
+        "day19" => Some(day19::render()),
+        "day20" => Some(day20::render(tick)),
+        _ => None
+    };
+
+    match (document, common::option_value(options, "--out")) {
+        (Some(document), Some(path)) => {
+            document.save(path).expect("Unable to write the file");
+            println!("Rendered {} to {}", day, path);
+        },
+        (Some(_), None) => eprintln!("Please specify the output file with --out file.svg"),
+        (None, _) => eprintln!("{} has no renderer", day)
+    }
+}
+
+fn export(day: &str, options: &[String]) {
+    let format = common::option_value(options, "--format").unwrap_or("tree");
+    let depth = common::option_value(options, "--depth").map(|s| s.parse::<usize>().expect("Invalid depth"));
+    let output = match day {
+        "day7" => day7::export(format, depth),
+        _ => {
+            eprintln!("{} has no exporter", day);
+            None
+        }
+    };
+
+    match (output, common::option_value(options, "--out")) {
+        (Some(output), Some(path)) => {
+            let mut file = File::create(path).expect("Unable to create the file");
+            writeln!(file, "{}", output).expect("Unable to write the file");
+            println!("Exported {} to {}", day, path);
+        },
+        (Some(output), None) => println!("{}", output),
+        (None, _) => {}
+    }
+}
+
+fn animate(day: &str, options: &[String]) {
+    let fps = common::option_value(options, "--fps").map(|s| s.parse::<f64>().expect("Invalid frame rate")).unwrap_or(10.0);
+    match day {
+        "day13" => day13::animate(fps),
+        "day16" => day16::animate(fps),
+        "day19" => day19::animate(fps),
+        "day20" => day20::animate(fps),
+        _ => eprintln!("{} has no animation", day)
+    }
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() == 2 {
-        let day: &str = args[1].as_str();
-        match day {
-            "day1" => day1::run(),
-            "day2" => day2::run(),
-            "day3" => day3::run(),
-            "day4" => day4::run(),
-            "day5" => day5::run(),
-            "day6" => day6::run(),
-            "day7" => day7::run(),
-            "day8" => day8::run(),
-            "day9" => day9::run(),
-            "day10" => day10::run(),
-            "day11" => day11::run(),
-            "day12" => day12::run(),
-            "day13" => day13::run(),
-            "day14" => day14::run(),
-            "day15" => day15::run(),
-            "day16" => day16::run(),
-            "day17" => day17::run(),
-            "day18" => day18::run(),
-            "day19" => day19::run(),
-            "day20" => day20::run(),
-            _ => eprintln!("{} is not implemented", day)
-        }
+    if args.len() >= 3 && args[1] == "render" {
+        render(args[2].as_str(), &args[3..]);
+    } else if args.len() >= 3 && args[1] == "export" {
+        export(args[2].as_str(), &args[3..]);
+    } else if args.len() >= 3 && args[1] == "animate" {
+        animate(args[2].as_str(), &args[3..]);
+    } else if args.len() >= 2 {
+        run(args[1].as_str(), &args[2..]);
     } else {
-        eprintln!("Please specify the dayN to be run");
+        eprintln!("Please specify the dayN to be run [options], render dayN --out file.svg [--tick N], export dayN [--format F] [--out file] or animate dayN [--fps N]");
     }
 }